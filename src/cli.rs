@@ -0,0 +1,750 @@
+use std::{
+    io::{self, BufRead},
+    net::{IpAddr, SocketAddr},
+    process::ExitCode,
+    time::Duration,
+};
+
+use crate::core::{
+    bench::{self, Bench},
+    engine,
+    ports::{PortParseError, Ports, Protocol},
+    iface::Interface,
+    proxy::Proxy,
+    report,
+    scanner::{self, AddressFamily, HostVerdict, PortStatus, Profile, ResultSink, ScanConfig, ScanResult},
+    target::{Target, TargetError, DEFAULT_HOST_CAP},
+};
+
+/// Env var holding the port spec used when a scan names no ports
+pub const PORTS_ENV: &str = "WISE1738_PORTS";
+
+// =======================
+// EXIT CODES
+// =======================
+/// At least one host answered (open or closed ports)
+pub const EXIT_UP: u8 = 0;
+/// Every host was down / unreachable (only filtered results)
+pub const EXIT_DOWN: u8 = 1;
+/// Bad arguments or targets; nothing was scanned
+pub const EXIT_USAGE: u8 = 2;
+
+const USAGE: &str = "Usage: wise1738 --cli <host[,host...]|cidr> [ports] [!ports] [udp] [--top <n>] \
+[--profile quick|default|thorough] [--timeout <dur>] [--retries <n>] [--retry-delay <dur>] [--workers <n>] [--rate <n>] [--fast] [--connect-only] [--open-only] [--banners] [-4|-6|--family any|v4|v6] [--host-timeout <dur>] [--proxy http://host:port] [--interface <name>] [--dns <ip[:port]>] [--exclude <hosts|cidrs>] [--max-hosts <n>] [--max-results <n>] [-v] [--json|--json-pretty]";
+
+const STDIN_USAGE: &str = "Usage: <targets> | wise1738 --stdin [ports] [flags]";
+
+/// `wise1738 --help`: every mode's usage line
+pub fn help() -> String {
+    format!(
+        "Usage: wise1738 [--inline] [--no-color] [<command>]  (TUI; e.g. wise1738 scan 10.0.0.1 80)\n{}\n{}\n{}",
+        USAGE, STDIN_USAGE, BENCH_USAGE
+    )
+}
+
+// =======================
+// SCAN ARGUMENTS
+// =======================
+/// Arguments of a scan, shared by the TUI `scan` command and the CLI
+pub struct ScanArgs<'a> {
+    pub hosts: &'a str,
+    /// Hosts / CIDR blocks to drop after expansion (`--exclude`)
+    pub exclude: Option<&'a str>,
+    /// Most hosts `hosts` may expand to (`--max-hosts`)
+    pub max_hosts: usize,
+    /// `None` when no ports were given; the caller picks the default
+    pub ports: Option<Ports>,
+    /// `udp` / `tcp` after the host; already applied to `ports`
+    pub protocol: Protocol,
+    /// Ports of a `!80,443` item after the host; already taken out of
+    /// `ports`, the caller takes them out of its default set
+    pub skip: Vec<u16>,
+    pub config: ScanConfig,
+    /// NDJSON output (CLI only)
+    pub json: bool,
+    /// `--json-pretty`: one indented JSON array instead of NDJSON lines
+    pub pretty: bool,
+}
+
+impl<'a> ScanArgs<'a> {
+    /// `<host> [ports]` or `-a <host> -p <ports>`, plus flags
+    pub fn parse(args: &[&'a str]) -> Result<Self, String> {
+        Self::parse_with(args, &ScanConfig::default())
+    }
+
+    /// [`ScanArgs::parse`] on top of `base` settings (the TUI's `set`
+    /// values); a `--profile` replaces them
+    pub fn parse_with(args: &[&'a str], base: &ScanConfig) -> Result<Self, String> {
+        Self::parse_with_ports(args, base, Ports::parse)
+    }
+
+    /// [`ScanArgs::parse_with`], turning the port spec into [`Ports`]
+    /// with `parse_ports` (the TUI answers a repeated spec from a cache)
+    pub fn parse_with_ports(
+        args: &[&'a str],
+        base: &ScanConfig,
+        parse_ports: impl FnOnce(&str) -> Result<Ports, PortParseError>,
+    ) -> Result<Self, String> {
+        let mut host = None;
+        let mut ports = None;
+        let mut top = None;
+
+        // The argument after flag `i`; a flag there means the value is
+        // missing (`-` alone is the all-ports keyword)
+        let value = |i: usize| match args.get(i + 1) {
+            Some(v) if !v.starts_with('-') || *v == "-" => Ok(*v),
+            _ => Err(format!("{} needs a value", args[i])),
+        };
+
+        // A profile presets everything; flags below override it,
+        // wherever --profile appears on the line
+        let profile = match args.iter().position(|a| *a == "--profile") {
+            Some(i) => {
+                let name = value(i)?;
+                Some(Profile::parse(name).ok_or_else(|| {
+                    format!("Unknown profile '{}' (quick, default, thorough)", name)
+                })?)
+            }
+            None => None,
+        };
+        let mut config = profile.map(Profile::config).unwrap_or_else(|| base.clone());
+        let mut json = false;
+        let mut pretty = false;
+        let mut exclude = None;
+        let mut max_hosts = DEFAULT_HOST_CAP;
+        let mut protocol = Protocol::Tcp;
+        let mut skip = Vec::new();
+
+        // ✅ Host (-a) va ports (-p) flaglarini aniqlash
+        // Flagsiz: <host> [ports]
+        let mut i = 0;
+        while i < args.len() {
+            match args[i] {
+                "-a" => {
+                    host = Some(value(i)?);
+                    i += 2;
+                }
+                "-p" => {
+                    ports = Some(value(i)?);
+                    i += 2;
+                }
+                "--top" => {
+                    top = Some(value(i)?);
+                    i += 2;
+                }
+                "--connect-only" => {
+                    config.connect_only = true;
+                    i += 1;
+                }
+                "--fast" => {
+                    config.fast = true;
+                    i += 1;
+                }
+                "--open-only" => {
+                    config.open_only = true;
+                    i += 1;
+                }
+                "--banners" => {
+                    config.banners = true;
+                    i += 1;
+                }
+                "-4" => {
+                    config.family = AddressFamily::V4;
+                    i += 1;
+                }
+                "-6" => {
+                    config.family = AddressFamily::V6;
+                    i += 1;
+                }
+                "--family" => {
+                    let raw = value(i)?;
+                    config.family =
+                        AddressFamily::parse(raw).ok_or_else(|| format!("Invalid --family '{}' (any, v4, v6)", raw))?;
+                    i += 2;
+                }
+                "-v" | "--verbose" => {
+                    config.verbose = true;
+                    i += 1;
+                }
+                "--host-timeout" => {
+                    let raw = value(i)?;
+                    let t = parse_duration(raw)
+                        .ok_or_else(|| format!("Invalid --host-timeout '{}' (e.g. 30s, 500ms)", raw))?;
+                    config.host_timeout = Some(t);
+                    i += 2;
+                }
+                "--proxy" => {
+                    config.proxy = Some(Proxy::parse(value(i)?)?);
+                    i += 2;
+                }
+                "--interface" => {
+                    config.interface = Some(Interface::lookup(value(i)?)?);
+                    i += 2;
+                }
+                "--dns" => {
+                    let raw = value(i)?;
+                    let server = parse_nameserver(raw)
+                        .ok_or_else(|| format!("Invalid --dns '{}' (e.g. 10.0.0.53, [fd00::53]:5353)", raw))?;
+                    config.resolver = Some(server);
+                    i += 2;
+                }
+                "--max-hosts" => {
+                    let raw = value(i)?;
+                    max_hosts = raw
+                        .parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| format!("Invalid --max-hosts '{}'", raw))?;
+                    i += 2;
+                }
+                "--exclude" | "--exclude-hosts" => {
+                    exclude = Some(value(i)?);
+                    i += 2;
+                }
+                "--profile" => i += 2,
+                "--timeout" => {
+                    let raw = value(i)?;
+                    let t = parse_duration(raw)
+                        .filter(|t| !t.is_zero())
+                        .ok_or_else(|| format!("Invalid --timeout '{}' (e.g. 300ms, 2s)", raw))?;
+                    config.timeout = Some(t);
+                    i += 2;
+                }
+                "--retries" => {
+                    let raw = value(i)?;
+                    config.retries = raw
+                        .parse()
+                        .map_err(|_| format!("Invalid --retries '{}' (0-255)", raw))?;
+                    i += 2;
+                }
+                "--retry-delay" => {
+                    let raw = value(i)?;
+                    config.retry_delay = parse_duration(raw)
+                        .ok_or_else(|| format!("Invalid --retry-delay '{}' (e.g. 200ms)", raw))?;
+                    i += 2;
+                }
+                "--workers" => {
+                    let raw = value(i)?;
+                    let n = parse_workers(raw).ok_or_else(|| format!("Invalid --workers '{}'", raw))?;
+                    config.workers = Some(n);
+                    i += 2;
+                }
+                "--rate" => {
+                    let raw = value(i)?;
+                    let n = parse_rate(raw).ok_or_else(|| format!("Invalid --rate '{}' (connects/sec, >= 1)", raw))?;
+                    config.max_rate = Some(n);
+                    i += 2;
+                }
+                "--max-results" => {
+                    let raw = value(i)?;
+                    let n = raw
+                        .parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| format!("Invalid --max-results '{}'", raw))?;
+                    config.max_results = Some(n);
+                    i += 2;
+                }
+                "--json" => {
+                    json = true;
+                    i += 1;
+                }
+                "--json-pretty" => {
+                    json = true;
+                    pretty = true;
+                    i += 1;
+                }
+                arg if host.is_some() && Protocol::parse(arg).is_some() => {
+                    protocol = Protocol::parse(arg).unwrap_or_default();
+                    i += 1;
+                }
+                // `scan host all !80,443`: every listed port is skipped
+                arg if host.is_some() && arg.starts_with('!') => {
+                    let listed = Ports::parse(&arg.replace('!', ""))
+                        .map_err(|e| format!("Invalid port exclusion '{}': {}", arg, e))?;
+                    skip.extend(listed.ports);
+                    i += 1;
+                }
+                // `-` alone is the all-ports keyword
+                arg if arg.starts_with('-') && arg != "-" => return Err(format!("Unknown flag '{}'", arg)),
+                arg if host.is_none() => {
+                    host = Some(arg);
+                    i += 1;
+                }
+                arg if ports.is_none() => {
+                    ports = Some(arg);
+                    i += 1;
+                }
+                arg => return Err(format!("Unexpected argument '{}'", arg)),
+            }
+        }
+
+        if config.proxy.is_some() && config.interface.is_some() {
+            return Err("--interface cannot be combined with --proxy".into());
+        }
+        if config.proxy.is_some() && protocol == Protocol::Udp {
+            return Err("--proxy tunnels TCP only; UDP scans go direct".into());
+        }
+
+        let Some(hosts) = host else {
+            return Err("Usage: scan <ip|domain> [ports] [!ports] [udp] | scan -a <ip|domain> -p <ports>".into());
+        };
+
+        let ports = match (ports, top) {
+            (Some(_), Some(_)) => return Err("Use either a port spec or --top <n>, not both".into()),
+            (Some(p), None) => {
+                Some(parse_ports(p).map_err(|e| format!("Invalid port spec: {}", e))?)
+            }
+            (None, Some(n)) => match n.parse::<usize>() {
+                Ok(n) if n > 0 => Some(Ports::top(n)),
+                _ => return Err("Usage: scan <host> --top <n>".into()),
+            },
+            (None, None) => profile.map(Profile::ports),
+        };
+        // `example.com:8080`: the hosts' own port joins the spec, or
+        // stands in for the default set when there is none
+        let embedded = Target::embedded_port(hosts).map_err(|e| format!("Invalid target: {}", e))?;
+        let ports = match (ports, embedded) {
+            (ports, None) => ports,
+            (Some(mut p), Some(port)) => {
+                if !p.ports.contains(&port) {
+                    p.ports.push(port);
+                    p.ports.sort_unstable();
+                }
+                Some(p)
+            }
+            (None, Some(port)) => Some(Ports::single(port)),
+        };
+        let ports = match ports {
+            Some(mut p) => {
+                p.exclude(&skip);
+                if p.ports.is_empty() {
+                    return Err(format!("Invalid port spec: {}", PortParseError::Empty));
+                }
+                Some(p)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            hosts,
+            exclude,
+            max_hosts,
+            ports: ports.map(|p| p.with_protocol(protocol)),
+            protocol,
+            skip,
+            config,
+            json,
+            pretty,
+        })
+    }
+
+    /// Expanded hosts minus `--exclude`
+    pub fn targets(&self) -> Result<Vec<Target>, TargetError> {
+        let targets = Target::expand_capped(self.hosts, self.max_hosts)?;
+        match self.exclude {
+            Some(spec) => Target::exclude(targets, spec),
+            None => Ok(targets),
+        }
+    }
+}
+
+// =======================
+// HEADLESS RUN
+// =======================
+/// `wise1738 --cli ...`: scan, print results to stdout, and exit with
+/// [`EXIT_UP`], [`EXIT_DOWN`] or [`EXIT_USAGE`].
+pub fn run(args: &[String]) -> ExitCode {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let scan = match ScanArgs::parse(&args) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let targets = match scan.targets() {
+        Ok(t) if !t.is_empty() => t,
+        Ok(_) => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+        Err(e) => {
+            eprintln!("Invalid target: {}", e);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let ports = scan.ports.unwrap_or_else(|| {
+        let mut ports = default_ports().with_protocol(scan.protocol);
+        ports.exclude(&scan.skip);
+        ports
+    });
+
+    // open-only is applied here so closed ports still count as "host up"
+    let open_only = scan.config.open_only;
+    let config = ScanConfig {
+        open_only: false,
+        ..scan.config
+    };
+    let (mut results, meta) = engine::run_with_config(&targets, ports, &config);
+    let up = hosts_up(&targets, &results);
+    let verdicts = scanner::host_verdicts(&mut results);
+
+    if open_only {
+        results.retain(|r| r.status == PortStatus::Open);
+    }
+
+    // One array, so several results (or hosts) are still one JSON value
+    if scan.pretty {
+        println!("{}", report::results_json_pretty(&results));
+    }
+    for r in results.iter().filter(|_| !scan.pretty) {
+        if scan.json {
+            println!("{}", report::result_json(r));
+        } else {
+            let mut line = format!(
+                "{:<15} {:<5} {:<8} {} {}",
+                r.host,
+                r.port_label(),
+                r.status.to_string(),
+                r.service,
+                r.detail.as_deref().unwrap_or("")
+            );
+            if let Some(banner) = &r.banner {
+                line = format!("{} \"{}\"", line.trim_end(), banner);
+            }
+            println!("{}", line.trim_end());
+        }
+    }
+
+    for e in &meta.errors {
+        eprintln!("warning: {}", e);
+    }
+    for line in verdict_lines(&verdicts) {
+        eprintln!("{}", line);
+    }
+    for info in &meta.hosts {
+        eprintln!("{}: {}", info.host, info);
+    }
+    // Results are grouped by host (engine order)
+    for host in results.chunk_by(|a, b| a.host == b.host) {
+        if let Some(os) = engine::os_guess(host) {
+            eprintln!("{}: OS guess {} (low confidence)", host[0].host, os);
+        }
+    }
+    eprintln!("{}", meta.metrics);
+    if let Some(hint) = meta.metrics.filtered_hint(&config) {
+        eprintln!("hint: {}", hint);
+    }
+    eprintln!(
+        "{} of {} hosts up, {} open ports, {}ms",
+        up,
+        targets.len(),
+        results.iter().filter(|r| r.status == PortStatus::Open).count(),
+        meta.duration_ms
+    );
+
+    ExitCode::from(if up > 0 { EXIT_UP } else { EXIT_DOWN })
+}
+
+// =======================
+// STDIN TARGETS
+// =======================
+/// `wise1738 --stdin [ports] [flags]`: targets from stdin, one per line
+/// (CIDR allowed, `#` comments skipped), NDJSON streamed to stdout as
+/// results arrive. Same exit codes as [`run`].
+pub fn run_stdin(args: &[String]) -> ExitCode {
+    let hosts = match read_targets(io::stdin().lock()) {
+        Ok(h) if !h.is_empty() => h,
+        Ok(_) => {
+            eprintln!("No targets on stdin");
+            return ExitCode::from(EXIT_USAGE);
+        }
+        Err(e) => {
+            eprintln!("Cannot read stdin: {}", e);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    // The stdin hosts take the place of the positional host
+    let mut line = vec![hosts.as_str()];
+    line.extend(args.iter().map(String::as_str));
+
+    let scan = match ScanArgs::parse(&line) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}\n{}", e, STDIN_USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    // Results stream out as they arrive, so NDJSON is the only format
+    if scan.json || scan.pretty {
+        eprintln!("--stdin always writes NDJSON; drop --json / --json-pretty\n{}", STDIN_USAGE);
+        return ExitCode::from(EXIT_USAGE);
+    }
+    let targets = match scan.targets() {
+        Ok(t) if !t.is_empty() => t,
+        Ok(_) => {
+            eprintln!("No targets on stdin");
+            return ExitCode::from(EXIT_USAGE);
+        }
+        Err(e) => {
+            eprintln!("Invalid target: {}", e);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    let ports = scan.ports.unwrap_or_else(|| {
+        let mut ports = default_ports().with_protocol(scan.protocol);
+        ports.exclude(&scan.skip);
+        ports
+    });
+
+    let mut sink = NdjsonSink {
+        open_only: scan.config.open_only,
+        up: Vec::new(),
+        open: 0,
+    };
+    let config = ScanConfig {
+        open_only: false,
+        ..scan.config
+    };
+    let meta = engine::run_into(&targets, &ports, &config, &mut sink);
+
+    for e in &meta.errors {
+        eprintln!("warning: {}", e);
+    }
+    for info in &meta.hosts {
+        eprintln!("{}: {}", info.host, info);
+    }
+    eprintln!("{}", meta.metrics);
+    if let Some(hint) = meta.metrics.filtered_hint(&config) {
+        eprintln!("hint: {}", hint);
+    }
+    eprintln!(
+        "{} of {} hosts up, {} open ports, {}ms",
+        sink.up.len(),
+        targets.len(),
+        sink.open,
+        meta.duration_ms
+    );
+
+    ExitCode::from(if sink.up.is_empty() { EXIT_DOWN } else { EXIT_UP })
+}
+
+// =======================
+// BENCHMARK
+// =======================
+const BENCH_USAGE: &str = "Usage: wise1738 --bench [ports] [--listeners <n>] [--rounds <n>] \
+[--workers <n>] [--timeout <dur>] [--retries <n>] [--fast]";
+
+/// `wise1738 --bench [ports] [flags]`: scan loopback (the given ports,
+/// mostly closed, plus local listeners) with each built-in backend and
+/// print a ports/sec table
+pub fn run_bench(args: &[String]) -> ExitCode {
+    let bench = match parse_bench(args) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("{}\n{}", e, BENCH_USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let rows = match bench::run(&bench) {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("Cannot start local listeners: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let workers = bench.config.workers.map_or("auto".to_string(), |w| w.to_string());
+    let timeout = format!("{}ms", bench.config.connect_timeout().as_millis());
+    println!(
+        "{:<8} {:>7} {:>7} {:>6} {:>7} {:>10} {:>10} {:>10}",
+        "BACKEND", "WORKERS", "TIMEOUT", "PORTS", "OPEN", "WORST/S", "MEDIAN/S", "BEST/S"
+    );
+    for row in &rows {
+        println!(
+            "{:<8} {:>7} {:>7} {:>6} {:>7} {:>10.0} {:>10.0} {:>10.0}",
+            row.backend.name(),
+            workers,
+            timeout,
+            row.ports,
+            format!("{}/{}", row.open, bench.listeners),
+            row.worst(),
+            row.median(),
+            row.best()
+        );
+    }
+    eprintln!("{} rounds per backend, connect-only on 127.0.0.1", bench.rounds);
+
+    // A listener missed means the numbers measured timeouts, not throughput
+    if rows.iter().any(|r| r.open < bench.listeners) {
+        eprintln!("warning: not every listener was found open; raise --timeout or scan with fewer connects at once (--workers)");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// `--listeners` / `--rounds`, then the usual scan flags on 127.0.0.1
+fn parse_bench(args: &[String]) -> Result<Bench, String> {
+    let mut bench = Bench::default();
+    let mut rest = vec!["127.0.0.1"];
+    let mut i = 0;
+
+    while i < args.len() {
+        match args[i].as_str() {
+            flag @ ("--listeners" | "--rounds") => {
+                let Some(raw) = args.get(i + 1).filter(|v| !v.starts_with('-')) else {
+                    return Err(format!("{} needs a value", flag));
+                };
+                let n = raw
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("Invalid {} '{}'", flag, raw))?;
+                match flag {
+                    "--listeners" => bench.listeners = n,
+                    _ => bench.rounds = n,
+                }
+                i += 2;
+            }
+            arg => {
+                rest.push(arg);
+                i += 1;
+            }
+        }
+    }
+
+    let scan = ScanArgs::parse(&rest)?;
+    if scan.config.proxy.is_some() {
+        return Err("--bench scans loopback directly; --proxy is not supported".into());
+    }
+    if scan.protocol == Protocol::Udp {
+        return Err("--bench measures TCP scans only".into());
+    }
+    if let Some(ports) = scan.ports {
+        bench.ports = ports;
+    }
+    bench.config = scan.config;
+    Ok(bench)
+}
+
+/// Non-blank, non-comment lines joined into one target expression
+fn read_targets(input: impl BufRead) -> io::Result<String> {
+    let mut hosts = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            hosts.push(line.to_string());
+        }
+    }
+    Ok(hosts.join(","))
+}
+
+/// Prints each result as one JSON line, counting hosts up on the way
+struct NdjsonSink {
+    open_only: bool,
+    up: Vec<String>,
+    open: usize,
+}
+
+impl ResultSink for NdjsonSink {
+    fn on_result(&mut self, r: &ScanResult) {
+        let answered = matches!(r.status, PortStatus::Open | PortStatus::Closed);
+        if answered && !self.up.contains(&r.host) {
+            self.up.push(r.host.clone());
+        }
+        if r.status == PortStatus::Open {
+            self.open += 1;
+        } else if self.open_only {
+            return;
+        }
+        println!("{}", report::result_json(r));
+    }
+}
+
+/// Colors are on unless `--no-color` was given or `NO_COLOR` is set
+/// to a non-empty value (<https://no-color.org>)
+pub fn color_enabled(no_color_flag: bool) -> bool {
+    !no_color_flag && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+/// Worker count, at least 1
+pub fn parse_workers(raw: &str) -> Option<usize> {
+    raw.parse().ok().filter(|&n| n > 0)
+}
+
+/// `--rate` / `set rate`: ports started per second, at least 1
+pub fn parse_rate(raw: &str) -> Option<u32> {
+    raw.parse().ok().filter(|&n| n > 0)
+}
+
+/// Nameserver address; port 53 unless given
+pub fn parse_nameserver(raw: &str) -> Option<SocketAddr> {
+    raw.parse::<SocketAddr>()
+        .ok()
+        .or_else(|| raw.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 53)))
+}
+
+/// `30s`, `500ms`, `2m`, or bare seconds (`30`)
+pub fn parse_duration(raw: &str) -> Option<Duration> {
+    let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+    let (num, unit) = raw.split_at(split);
+    let n: u64 = num.parse().ok()?;
+
+    match unit {
+        "" | "s" => Some(Duration::from_secs(n)),
+        "ms" => Some(Duration::from_millis(n)),
+        "m" => Some(Duration::from_secs(n * 60)),
+        _ => None,
+    }
+}
+
+/// Ports for a scan that named none: [`PORTS_ENV`], else top_basic
+fn default_ports() -> Ports {
+    match env_default_ports() {
+        Some(Ok(p)) => p,
+        Some(Err(e)) => {
+            eprintln!("Ignoring {} ({}), default is top_basic", PORTS_ENV, e);
+            Ports::top_basic()
+        }
+        None => Ports::top_basic(),
+    }
+}
+
+/// Default ports from [`PORTS_ENV`], if set
+pub fn env_default_ports() -> Option<Result<Ports, PortParseError>> {
+    std::env::var(PORTS_ENV).ok().map(|spec| Ports::parse(&spec))
+}
+
+/// Host verdicts worth reporting; down hosts are one count line when
+/// there are several
+pub fn verdict_lines(verdicts: &[(String, HostVerdict)]) -> Vec<String> {
+    let down = verdicts.iter().filter(|v| v.1 == HostVerdict::Down).count();
+    let mut lines: Vec<String> = verdicts
+        .iter()
+        .filter(|(_, v)| *v != HostVerdict::Up && (*v != HostVerdict::Down || down == 1))
+        .map(|(host, v)| format!("{}: {}", host, v))
+        .collect();
+    if down > 1 {
+        lines.push(format!("{} hosts: {}", down, HostVerdict::Down));
+    }
+    lines
+}
+
+/// A host is up if any port answered, open or closed (a RST is an answer)
+fn hosts_up(targets: &[Target], results: &[ScanResult]) -> usize {
+    targets
+        .iter()
+        .filter(|t| {
+            results
+                .iter()
+                .any(|r| r.host == t.host && matches!(r.status, PortStatus::Open | PortStatus::Closed))
+        })
+        .count()
+}
@@ -0,0 +1,241 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    net::{lookup_host, TcpSocket},
+    runtime,
+    sync::Semaphore,
+    task::JoinSet,
+    time::timeout,
+};
+
+use crate::core::{
+    ports::Ports,
+    scanner::{
+        self, Answered, HostSetup, Meter, ResultSink, ScanConfig, ScanError, ScanMetrics, ScanResult, TcpResult,
+        Throttle,
+    },
+    target::Target,
+};
+
+/// Connects in flight at once, unless `ScanConfig::workers` says otherwise
+pub(crate) const CONCURRENCY: usize = 2048;
+/// Runtime threads; connects are I/O bound, probes go to the blocking pool
+const RUNTIME_THREADS: usize = 4;
+
+// =======================
+// ENTRY
+// =======================
+/// Tokio-driven scan. Same classification as the threaded backend, but
+/// thousands of connects share a small runtime instead of one blocked
+/// thread each. Service probes on open ports still run blocking, on
+/// tokio's blocking pool.
+pub fn scan<S: ResultSink>(
+    target: &Target,
+    ports: &Ports,
+    config: &ScanConfig,
+    sink: &mut S,
+    metrics: &mut ScanMetrics,
+) -> Result<(), ScanError> {
+    let rt = match runtime::Builder::new_multi_thread()
+        .worker_threads(RUNTIME_THREADS)
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(_) => {
+            return match &config.interface {
+                Some(iface) => scanner::scan_threads(target, ports, config, iface, sink, metrics),
+                None => scanner::scan_threads(target, ports, config, &scanner::RealConnector, sink, metrics),
+            };
+        }
+    };
+
+    // block_on polls on this thread, so the sink needs no Send bound
+    rt.block_on(scan_async(
+        target.host.clone(),
+        ports.ports.clone(),
+        Arc::new(config.clone()),
+        sink,
+        metrics,
+    ))
+}
+
+async fn scan_async<S: ResultSink>(
+    host: String,
+    ports: Vec<u16>,
+    config: Arc<ScanConfig>,
+    sink: &mut S,
+    metrics: &mut ScanMetrics,
+) -> Result<(), ScanError> {
+    // Resolve once, for every port
+    let ips = match lookup(&host, &config).await {
+        Ok(ips) if !ips.is_empty() => ips,
+        Ok(_) => return Err(unresolved(&host, "no addresses".into())),
+        Err(e) => return Err(unresolved(&host, e.to_string())),
+    };
+
+    let ips = scanner::of_family(&config, &host, ips)?;
+    let ips = scanner::bindable(&config, &host, ips)?;
+    let setup = Arc::new(HostSetup::new(&host, ips, &config));
+    let host = Arc::new(host);
+    let limit = Arc::new(Semaphore::new(config.workers.unwrap_or(CONCURRENCY).max(1)));
+    let meter = Arc::new(Meter::default());
+    let deadline = config.host_timeout.map(|t| Instant::now() + t);
+    let throttle = Throttle::new(&config).map(Arc::new);
+    let mut tasks = JoinSet::new();
+
+    for port in ports {
+        let (host, setup, limit, config) = (host.clone(), setup.clone(), limit.clone(), config.clone());
+        let (meter, throttle) = (meter.clone(), throttle.clone());
+
+        tasks.spawn(async move {
+            let _permit = limit.acquire_owned().await;
+            while config.pause.is_paused() && !config.cancel.is_cancelled() {
+                tokio::time::sleep(scanner::PAUSE_POLL).await;
+            }
+            if let Some(slot) = throttle.map(|t| t.slot()) {
+                while Instant::now() < slot && !config.cancel.is_cancelled() {
+                    tokio::time::sleep(slot.saturating_duration_since(Instant::now()).min(scanner::PAUSE_POLL)).await;
+                }
+            }
+            // Cancelled ports give no result at all
+            if config.cancel.is_cancelled() {
+                return None;
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return Some(scanner::skipped(&host, port));
+            }
+            Some(scan_single(host, setup, port, config, &meter).await)
+        });
+    }
+
+    let mut failed = 0;
+    let mut answered = Answered::default();
+    while let Some(r) = tasks.join_next().await {
+        match r {
+            Ok(None) => {}
+            Ok(Some(r)) => {
+                metrics.count(&r);
+                answered.see(&setup, &r);
+                if scanner::keep(&config, &r) {
+                    sink.on_result(&r);
+                }
+            }
+            Err(_) => failed += 1,
+        }
+        if !sink.wants_more() {
+            tasks.abort_all();
+            meter.add_to(metrics);
+            return Err(ScanError::ResultLimit);
+        }
+    }
+    meter.add_to(metrics);
+    if config.cancel.is_cancelled() {
+        return Err(ScanError::Cancelled);
+    }
+
+    if let Some(addr) = answered.addr() {
+        let measure = tokio::task::spawn_blocking(move || scanner::host_info(&config, &setup, &host, addr));
+        if let Ok(Some(info)) = measure.await {
+            sink.on_host(&info);
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        n => Err(ScanError::WorkerPanicked { workers: n }),
+    }
+}
+
+// =======================
+// CORE LOGIC
+// =======================
+async fn scan_single(
+    host: Arc<String>,
+    setup: Arc<HostSetup>,
+    port: u16,
+    config: Arc<ScanConfig>,
+    meter: &Meter,
+) -> ScanResult {
+    let mut saw_timeout = false;
+    let mut saw_refusal = false;
+    let mut failure = None;
+
+    'ips: for &ip in &setup.ips {
+        let addr = SocketAddr::new(ip, port);
+
+        for attempt in 0..=config.retries {
+            if attempt > 0 && !config.retry_delay.is_zero() {
+                tokio::time::sleep(config.retry_delay).await;
+            }
+            let bind = config.interface.as_ref().and_then(|i| i.bind_addr(ip));
+            let started = Instant::now();
+            let outcome = connect(addr, bind, config.connect_timeout()).await;
+            meter.record(&outcome);
+            match outcome {
+                TcpResult::Open(local) => {
+                    let (probe_host, setup, latency) = (host.clone(), setup.clone(), started.elapsed());
+                    let probe = tokio::task::spawn_blocking(move || {
+                        scanner::open_result(&config, &setup, addr, local, latency, &probe_host, port)
+                    });
+                    return match probe.await {
+                        Ok(r) => r,
+                        Err(_) => scanner::unanswered(&host, port, false),
+                    };
+                }
+                TcpResult::Timeout => saw_timeout = true,
+                TcpResult::Refused => {
+                    saw_refusal = true;
+                    continue 'ips;
+                }
+                TcpResult::Unreachable(kind) => {
+                    failure = Some(kind);
+                    continue 'ips;
+                }
+                TcpResult::ProxyDenied(_) => return scanner::proxy_denied(&host, port),
+            }
+        }
+    }
+
+    match failure {
+        Some(kind) if !saw_timeout && !saw_refusal => scanner::unreached(&host, port, kind),
+        _ => scanner::unanswered(&host, port, saw_timeout),
+    }
+}
+
+fn unresolved(host: &str, reason: String) -> ScanError {
+    ScanError::Unresolved {
+        host: host.to_string(),
+        reason,
+    }
+}
+
+async fn lookup(host: &str, config: &Arc<ScanConfig>) -> std::io::Result<Vec<IpAddr>> {
+    if config.resolver.is_none() {
+        return Ok(lookup_host((host, 0)).await?.map(|a| a.ip()).collect());
+    }
+    let (host, config) = (host.to_string(), config.clone());
+    tokio::task::spawn_blocking(move || scanner::resolve(&config, &host))
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+/// Connect from `bind` (port chosen by the OS) when given
+async fn connect(addr: SocketAddr, bind: Option<IpAddr>, limit: Duration) -> TcpResult {
+    let attempt = async {
+        let socket = if addr.is_ipv4() { TcpSocket::new_v4()? } else { TcpSocket::new_v6()? };
+        if let Some(ip) = bind {
+            socket.bind(SocketAddr::new(ip, 0))?;
+        }
+        socket.connect(addr).await
+    };
+    match timeout(limit, attempt).await {
+        Ok(Ok(s)) => TcpResult::Open(s.local_addr().ok()),
+        Ok(Err(e)) => scanner::classify_error(&e),
+        Err(_) => TcpResult::Timeout,
+    }
+}
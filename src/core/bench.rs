@@ -0,0 +1,195 @@
+use std::{
+    io,
+    net::{Ipv4Addr, TcpListener},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::core::{
+    engine,
+    ports::Ports,
+    scanner::{Backend, PortStatus, ScanConfig},
+    target::Target,
+};
+
+/// Closed ports scanned besides the listeners when none are given
+pub const BENCH_PORTS: (u16, u16) = (20000, 24095);
+/// Local listeners (known-open ports) by default
+pub const BENCH_LISTENERS: usize = 32;
+/// Runs per backend; the table shows the median
+pub const BENCH_ROUNDS: usize = 5;
+
+/// What `--bench` scans and how often
+#[derive(Clone, Debug)]
+pub struct Bench {
+    /// Ports scanned on loopback besides the listeners (mostly closed)
+    pub ports: Ports,
+    pub listeners: usize,
+    pub rounds: usize,
+    /// Worker / timeout / retry settings under test
+    pub config: ScanConfig,
+}
+
+impl Default for Bench {
+    fn default() -> Self {
+        Self {
+            ports: Ports::range(BENCH_PORTS.0, BENCH_PORTS.1),
+            listeners: BENCH_LISTENERS,
+            rounds: BENCH_ROUNDS,
+            config: ScanConfig::default(),
+        }
+    }
+}
+
+/// One table row: a backend's runs over the same ports
+#[derive(Clone, Debug)]
+pub struct BenchRow {
+    pub backend: Backend,
+    pub ports: usize,
+    /// Listeners found open in every run (should equal the listener count)
+    pub open: usize,
+    /// Ports/sec of each run, sorted ascending
+    pub runs: Vec<f64>,
+}
+
+impl BenchRow {
+    pub fn median(&self) -> f64 {
+        self.runs.get(self.runs.len() / 2).copied().unwrap_or(0.0)
+    }
+
+    pub fn best(&self) -> f64 {
+        self.runs.last().copied().unwrap_or(0.0)
+    }
+
+    pub fn worst(&self) -> f64 {
+        self.runs.first().copied().unwrap_or(0.0)
+    }
+}
+
+/// Listeners on 127.0.0.1 that accept and drop connections until
+/// dropped, so the accept queue never fills between runs
+pub struct Listeners {
+    pub ports: Vec<u16>,
+    stop: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl Listeners {
+    pub fn bind(count: usize) -> io::Result<Listeners> {
+        let listeners = (0..count)
+            .map(|_| {
+                let l = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+                l.set_nonblocking(true)?;
+                Ok(l)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let ports = listeners
+            .iter()
+            .map(|l| l.local_addr().map(|a| a.port()))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let acceptor = thread::spawn(move || {
+            while !flag.load(Ordering::Relaxed) {
+                for l in &listeners {
+                    while l.accept().is_ok() {}
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+
+        Ok(Listeners {
+            ports,
+            stop,
+            acceptor: Some(acceptor),
+        })
+    }
+}
+
+impl Drop for Listeners {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+}
+
+/// Backends built into this binary, threaded first
+pub fn backends() -> Vec<Backend> {
+    vec![
+        Backend::Threads,
+        #[cfg(feature = "async")]
+        Backend::Async,
+    ]
+}
+
+/// Scan loopback `rounds` times per backend: the bench ports plus the
+/// listeners, connect-only (probes would measure their own timeouts)
+pub fn run(bench: &Bench) -> io::Result<Vec<BenchRow>> {
+    let listeners = Listeners::bind(bench.listeners)?;
+    let mut list = bench.ports.ports.clone();
+    list.extend(&listeners.ports);
+    list.sort_unstable();
+    list.dedup();
+    let ports = Ports::multiple(list);
+    let target = [Target::new("127.0.0.1")];
+
+    let rows = backends()
+        .into_iter()
+        .map(|backend| {
+            let config = ScanConfig {
+                backend,
+                connect_only: true,
+                ..bench.config.clone()
+            };
+            let mut row = BenchRow {
+                backend,
+                ports: ports.ports.len(),
+                open: usize::MAX,
+                runs: Vec::with_capacity(bench.rounds),
+            };
+            for _ in 0..bench.rounds.max(1) {
+                let (results, meta) = engine::run_with_config(&target, ports.clone(), &config);
+                let open = results
+                    .iter()
+                    .filter(|r| r.status == PortStatus::Open && listeners.ports.contains(&r.port))
+                    .count();
+                row.open = row.open.min(open);
+                row.runs.push(meta.metrics.ports_per_sec());
+            }
+            row.runs.sort_by(f64::total_cmp);
+            row
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_every_listener() {
+        let bench = Bench {
+            ports: Ports::range(BENCH_PORTS.0, BENCH_PORTS.0 + 63),
+            listeners: 4,
+            rounds: 2,
+            ..Default::default()
+        };
+        let rows = run(&bench).unwrap();
+
+        assert_eq!(rows.len(), backends().len());
+        for row in &rows {
+            assert_eq!(row.open, 4);
+            assert_eq!(row.runs.len(), 2);
+            assert!(row.worst() <= row.median() && row.median() <= row.best());
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+// =======================
+// LOOKUP
+// =======================
+/// A + AAAA lookup of `name` against one nameserver over UDP (recursion
+/// desired). For split-horizon setups where the system resolver gives
+/// the wrong answers.
+pub fn lookup(server: SocketAddr, name: &str, timeout: Duration) -> io::Result<Vec<IpAddr>> {
+    let bind: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.connect(server)?;
+    socket.set_read_timeout(Some(timeout))?;
+
+    let id = query_id();
+    let queries = [(id, TYPE_A), (id.wrapping_add(1), TYPE_AAAA)];
+    for &(id, qtype) in &queries {
+        socket.send(&query(id, name, qtype)?)?;
+    }
+
+    let mut ips = Vec::new();
+    let mut pending: Vec<u16> = queries.iter().map(|q| q.0).collect();
+    let mut buf = [0u8; 1500];
+
+    while !pending.is_empty() {
+        let n = match socket.recv(&mut buf) {
+            Ok(n) => n,
+            // One family unanswered: keep what the other gave us
+            Err(_) if !ips.is_empty() => break,
+            Err(e) => return Err(e),
+        };
+        if let Some((id, answers)) = parse_response(&buf[..n])
+            && let Some(i) = pending.iter().position(|&p| p == id)
+        {
+            pending.remove(i);
+            ips.extend(answers);
+        }
+    }
+
+    if ips.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} has no A/AAAA records at {}", name, server),
+        ));
+    }
+    Ok(ips)
+}
+
+fn query_id() -> u16 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u16)
+        .unwrap_or(0x1738)
+}
+
+// =======================
+// WIRE FORMAT
+// =======================
+fn query(id: u16, name: &str, qtype: u16) -> io::Result<Vec<u8>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid name '{}'", name));
+
+    // Header: id, RD flag, 1 question
+    let mut q = Vec::with_capacity(name.len() + 18);
+    q.extend_from_slice(&id.to_be_bytes());
+    q.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 || !label.is_ascii() {
+            return Err(invalid());
+        }
+        q.push(label.len() as u8);
+        q.extend_from_slice(label.as_bytes());
+    }
+    q.push(0);
+    q.extend_from_slice(&qtype.to_be_bytes());
+    q.extend_from_slice(&1u16.to_be_bytes());
+    Ok(q)
+}
+
+/// Query id and the A/AAAA addresses in the answer section (CNAMEs and
+/// other records are skipped)
+fn parse_response(msg: &[u8]) -> Option<(u16, Vec<IpAddr>)> {
+    let id = u16::from_be_bytes([*msg.first()?, *msg.get(1)?]);
+    let is_response = msg.get(2)? & 0x80 != 0;
+    if !is_response {
+        return None;
+    }
+    let qdcount = u16::from_be_bytes([*msg.get(4)?, *msg.get(5)?]);
+    let ancount = u16::from_be_bytes([*msg.get(6)?, *msg.get(7)?]);
+
+    let mut pos = 12;
+    for _ in 0..qdcount {
+        pos = skip_name(msg, pos)? + 4;
+    }
+
+    let mut ips = Vec::new();
+    for _ in 0..ancount {
+        pos = skip_name(msg, pos)?;
+        let field = |at: usize| Some(u16::from_be_bytes([*msg.get(at)?, *msg.get(at + 1)?]));
+        let rtype = field(pos)?;
+        let rdlen = field(pos + 8)? as usize;
+        let data = msg.get(pos + 10..pos + 10 + rdlen)?;
+
+        match (rtype, data.len()) {
+            (TYPE_A, 4) => ips.push(IpAddr::from(<[u8; 4]>::try_from(data).ok()?)),
+            (TYPE_AAAA, 16) => ips.push(IpAddr::from(<[u8; 16]>::try_from(data).ok()?)),
+            _ => {}
+        }
+        pos += 10 + rdlen;
+    }
+
+    Some((id, ips))
+}
+
+/// Position just past a (possibly compressed) name
+fn skip_name(msg: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            l if l & 0xc0 == 0xc0 => return Some(pos + 2),
+            l => pos += 1 + l as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_query() {
+        let q = query(0xabcd, "db.corp.", TYPE_AAAA).unwrap();
+
+        assert_eq!(&q[..2], &[0xab, 0xcd]);
+        assert_eq!(&q[12..], b"\x02db\x04corp\x00\x00\x1c\x00\x01");
+        assert!(query(1, "a..b", TYPE_A).is_err());
+    }
+
+    #[test]
+    fn parses_answers_through_cname() {
+        let mut msg = query(7, "www.corp", TYPE_A).unwrap();
+        msg[2] |= 0x80; // response
+        msg[7] = 2; // two answers
+        // www.corp CNAME host.corp (name as a pointer to the question)
+        msg.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 12]);
+        // A 10.1.2.3
+        msg.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 10, 1, 2, 3]);
+
+        let (id, ips) = parse_response(&msg).unwrap();
+
+        assert_eq!(id, 7);
+        assert_eq!(ips, vec![IpAddr::from([10, 1, 2, 3])]);
+    }
+}
@@ -1,10 +1,11 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use chrono::{DateTime, Utc};
 
 use crate::core::{
-    ports::Ports,
-    scanner::{self, ScanResult},
+    ports::{Ports, Protocol},
+    report,
+    scanner::{self, AddressFamily, HostInfo, PortStatus, ResultSink, ScanConfig, ScanError, ScanMetrics, ScanResult},
     target::Target,
 };
 
@@ -12,13 +13,183 @@ use crate::core::{
 /// Vazifasi:
 /// - target yaratish
 /// - scanner ishga tushirish
-/// - natijani o‘zgartirmasdan qaytarish
+/// - natijani o‘zgartirmasdan, vaqt ma’lumoti bilan [`ScanReport`] qilib qaytarish
 ///
-/// ⚠️ v0.1.x API — SAQLANADI
-pub fn run(target_input: &str, ports: Ports) -> Vec<ScanResult> {
-    let target = Target::new(target_input);
+/// ⚠️ v0.1.x kodi uchun: `run(..).results()` — avvalgi natijalar ro‘yxati
+pub fn run(target_input: &str, ports: Ports) -> ScanReport {
+    ScanBuilder::new(target_input).ports(ports).run()
+}
+
+/// [`run`] natijalarini JSON matn sifatida (`{"results":[...]}`),
+/// har bir skanerlangan port bilan — faqat ochiqlari emas.
+/// jq va skriptlar uchun.
+pub fn run_to_json(target_input: &str, ports: Ports) -> String {
+    report::to_json(run(target_input, ports).results())
+}
 
-    scanner::scan(&target, &ports)
+/// Bitta skan — kutubxona sifatida ishlatish uchun. TUI qiladigan
+/// guruhlashni (ochiq / yopiq / filtrlangan) qayta yozish shart emas.
+#[derive(Debug, Clone)]
+pub struct ScanReport {
+    /// Kiritilganidek (`example.com`, `[::1]`)
+    pub target: String,
+    pub started_at: DateTime<Utc>,
+    pub duration: Duration,
+    /// Har bir skanerlangan port, port bo‘yicha tartiblangan
+    pub results: Vec<ScanResult>,
+    /// Skan xatolari: host resolve bo‘lmadi, oilasi mos emas va h.k.
+    /// Bo‘sh `results` sababi shu yerda
+    pub errors: Vec<ScanError>,
+}
+
+impl ScanReport {
+    /// Barcha natijalar (avvalgi `Vec<ScanResult>` qaytarilgan joylar uchun)
+    pub fn results(&self) -> &[ScanResult] {
+        &self.results
+    }
+
+    pub fn open(&self) -> Vec<&ScanResult> {
+        self.with_status(PortStatus::Open)
+    }
+
+    pub fn closed(&self) -> Vec<&ScanResult> {
+        self.with_status(PortStatus::Closed)
+    }
+
+    /// Faqat `Filtered`; UDP'ning `Open|Filtered` natijalari bu yerga kirmaydi
+    pub fn filtered(&self) -> Vec<&ScanResult> {
+        self.with_status(PortStatus::Filtered)
+    }
+
+    fn with_status(&self, status: PortStatus) -> Vec<&ScanResult> {
+        self.results.iter().filter(|r| r.status == status).collect()
+    }
+}
+
+// =======================
+// BUILDER
+// =======================
+/// Skanni zanjir bilan sozlash: har bir setter `self` qaytaradi,
+/// berilmaganlari — standart qiymatlar ([`ScanConfig::default`],
+/// portlar `top_basic`). Hostdagi port (`x.com:8080`) berilgan
+/// portlarga qo‘shiladi, portlar berilmagan bo‘lsa — faqat o‘sha port.
+/// [`run`] bilan bir xil [`ScanReport`] qaytaradi.
+///
+/// ```
+/// use std::{net::TcpListener, time::Duration};
+/// use wise1738::core::{engine::ScanBuilder, ports::Ports};
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let port = listener.local_addr().unwrap().port();
+///
+/// let report = ScanBuilder::new("127.0.0.1")
+///     .ports(Ports::multiple(vec![port, 1]))
+///     .timeout(Duration::from_millis(300))
+///     .workers(128)
+///     .run();
+///
+/// assert_eq!(report.open()[0].port, port);
+/// assert_eq!(report.closed()[0].port, 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ScanBuilder {
+    host: String,
+    ports: Option<Ports>,
+    protocol: Option<Protocol>,
+    config: ScanConfig,
+}
+
+impl ScanBuilder {
+    pub fn new(host: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            ports: None,
+            protocol: None,
+            config: ScanConfig::default(),
+        }
+    }
+
+    pub fn ports(mut self, ports: Ports) -> Self {
+        self.ports = Some(ports);
+        self
+    }
+
+    /// Portlar protokolini almashtiradi (`Ports::with_protocol`)
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.config.workers = Some(workers);
+        self
+    }
+
+    pub fn retries(mut self, retries: u8) -> Self {
+        self.config.retries = retries;
+        self
+    }
+
+    /// Sekundiga eng ko‘p shuncha port ([`ScanConfig::max_rate`])
+    pub fn rate(mut self, per_sec: u32) -> Self {
+        self.config.max_rate = Some(per_sec);
+        self
+    }
+
+    pub fn family(mut self, family: AddressFamily) -> Self {
+        self.config.family = family;
+        self
+    }
+
+    /// Faqat ulanish — servis probelarisiz (tezroq)
+    pub fn connect_only(mut self, on: bool) -> Self {
+        self.config.connect_only = on;
+        self
+    }
+
+    /// Qolgan sozlamalar uchun: oldingi setterlar berganlari almashtiriladi
+    pub fn config(mut self, config: ScanConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Skan qilinmagan host sababi (resolve bo‘lmadi va h.k.) —
+    /// [`ScanReport::errors`] da
+    pub fn run(self) -> ScanReport {
+        let target = Target::new(&self.host);
+        let mut ports = match (self.ports, target.port) {
+            (Some(mut ports), Some(port)) => {
+                if !ports.ports.contains(&port) {
+                    ports.ports.push(port);
+                    ports.ports.sort_unstable();
+                }
+                ports
+            }
+            (Some(ports), None) => ports,
+            (None, Some(port)) => Ports::single(port),
+            (None, None) => Ports::top_basic(),
+        };
+        if let Some(protocol) = self.protocol {
+            ports = ports.with_protocol(protocol);
+        }
+        let started_at = Utc::now();
+        let timer = Instant::now();
+
+        let (results, meta) = run_with_config(std::slice::from_ref(&target), ports, &self.config);
+
+        ScanReport {
+            target: target.name,
+            started_at,
+            duration: timer.elapsed(),
+            results,
+            errors: meta.errors,
+        }
+    }
 }
 
 // =======================
@@ -31,6 +202,35 @@ pub struct ScanMeta {
     pub target: String,
     pub started_at: DateTime<Utc>,
     pub duration_ms: u128,
+    /// Scan xatolari (masalan, worker panic) — natijalar to‘liq emas
+    pub errors: Vec<ScanError>,
+    /// Hisoblagichlar: connectlar, timeoutlar, port/sek
+    pub metrics: ScanMetrics,
+    /// Javob bergan hostlar TTL / masofasi (faqat verbose, Linux)
+    pub hosts: Vec<HostInfo>,
+}
+
+/// Sinkdan o‘tayotgan host ma’lumotlarini ScanMeta uchun yig‘adi
+struct HostTap<'a, S> {
+    sink: &'a mut S,
+    hosts: Vec<HostInfo>,
+}
+
+impl<S: ResultSink> ResultSink for HostTap<'_, S> {
+    fn on_result(&mut self, result: &ScanResult) {
+        self.sink.on_result(result);
+    }
+
+    fn wants_more(&self) -> bool {
+        self.sink.wants_more()
+    }
+
+    fn on_host(&mut self, info: &HostInfo) {
+        // Fast rejimda host ikki marta o‘lchanishi mumkin — oxirgisi qoladi
+        self.hosts.retain(|h| h.host != info.host);
+        self.hosts.push(info.clone());
+        self.sink.on_host(info);
+    }
 }
 
 /// Kengaytirilgan run — meta bilan
@@ -41,17 +241,117 @@ pub fn run_with_meta(
     target_input: &str,
     ports: Ports,
 ) -> (Vec<ScanResult>, ScanMeta) {
+    // "a,b,c" yoki CIDR → bir nechta host; xato bo‘lsa — bitta host sifatida
+    let targets = Target::expand(target_input).unwrap_or_else(|_| vec![Target::new(target_input)]);
+
+    run_with_config(&targets, ports, &ScanConfig::default())
+}
+
+// =======================
+// OS TAXMINI
+// =======================
+/// Bannerlardagi so‘z → OS (aniqrog‘i oldinda: "ubuntu" "linux"dan oldin)
+const BANNER_OS: [(&str, &str); 12] = [
+    ("ubuntu", "Linux (Ubuntu)"),
+    ("debian", "Linux (Debian)"),
+    ("+deb", "Linux (Debian)"),
+    ("centos", "Linux (CentOS)"),
+    ("red hat", "Linux (Red Hat)"),
+    ("fedora", "Linux (Fedora)"),
+    ("alpine", "Linux (Alpine)"),
+    ("linux", "Linux"),
+    ("freebsd", "FreeBSD"),
+    ("openbsd", "OpenBSD"),
+    ("microsoft", "Windows"),
+    ("windows", "Windows"),
+];
+
+/// Windowsga xos portlar: RPC, NetBIOS, SMB, RDP
+const WINDOWS_PORTS: [u16; 4] = [135, 139, 445, 3389];
+
+/// Bitta host natijalaridan qo‘pol OS taxmini — faqat ochiq portlar va
+/// ular bannerlari asosida. Ishonch past: ko‘rsatganda "guess" deb
+/// belgilang.
+pub fn os_guess(results: &[ScanResult]) -> Option<String> {
+    let open: Vec<&ScanResult> = results.iter().filter(|r| r.status == PortStatus::Open).collect();
+
+    // Banner OSni o‘zi aytsa — shu eng kuchli belgi. SSH serverining
+    // identifikatsiya satri (`SSH-2.0-OpenSSH_9.6 Ubuntu-3`) service'da turadi
+    for r in &open {
+        for banner in r.detail.iter().chain([&r.service]) {
+            let banner = banner.to_ascii_lowercase();
+            if let Some((_, os)) = BANNER_OS.iter().find(|(word, _)| banner.contains(word)) {
+                return Some(os.to_string());
+            }
+        }
+    }
+
+    let windows = open.iter().filter(|r| WINDOWS_PORTS.contains(&r.port)).count();
+    let unix = open.iter().filter(|r| r.port == 22 || r.os_hint == Some("Unix-like")).count();
+    match (windows, unix) {
+        (0, 0) => None,
+        (w, u) if w >= 2 && w > u => Some("Windows".into()),
+        (0, _) => Some("Unix-like".into()),
+        (_, 0) => Some("Windows".into()),
+        // Ikkala tomonda ham belgi bor — taxmin qilmaymiz
+        _ => None,
+    }
+}
+
+/// Tayyor target ro‘yxati + sozlamalar (connect-only va h.k.)
+pub fn run_with_config(
+    targets: &[Target],
+    ports: Ports,
+    config: &ScanConfig,
+) -> (Vec<ScanResult>, ScanMeta) {
+    let mut collector = scanner::Collector::new(config.result_cap());
+    let meta = run_into(targets, &ports, config, &mut collector);
+    scanner::sort_results(&mut collector.results);
+    if !config.open_only {
+        scanner::host_verdicts(&mut collector.results);
+    }
+
+    (collector.results, meta)
+}
+
+/// Natijalarni kelishi bilan sink'ga uzatish (host ichida tartiblanmagan).
+/// Hostlar baribir ketma-ket skanerlanadi.
+pub fn run_into<S: ResultSink>(
+    targets: &[Target],
+    ports: &Ports,
+    config: &ScanConfig,
+    sink: &mut S,
+) -> ScanMeta {
     let started_at = Utc::now();
     let timer = Instant::now();
 
-    let target = Target::new(target_input);
-    let results = scanner::scan(&target, &ports);
+    let mut errors = Vec::new();
+    let mut metrics = ScanMetrics::default();
+    let sink = &mut HostTap {
+        sink,
+        hosts: Vec::new(),
+    };
+    for t in targets {
+        if let Err(e) = scanner::scan_into_metered(t, ports, config, sink, &mut metrics) {
+            // Sink to‘ldi yoki skan bekor qilindi — qolgan hostlarni skanerlash shart emas
+            let stop = matches!(e, ScanError::ResultLimit | ScanError::Cancelled);
+            errors.push(e);
+            if stop {
+                break;
+            }
+        }
+    }
 
-    let meta = ScanMeta {
-        target: target_input.to_string(),
+    ScanMeta {
+        target: targets
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect::<Vec<_>>()
+            .join(","),
         started_at,
         duration_ms: timer.elapsed().as_millis(),
-    };
-
-    (results, meta)
+        errors,
+        metrics,
+        hosts: std::mem::take(&mut sink.hosts),
+    }
 }
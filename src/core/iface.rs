@@ -0,0 +1,118 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream},
+    time::Duration,
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::core::scanner::{classify_error, Connector, TcpResult};
+
+/// Network interface the scan binds its connects to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Interface {
+    pub name: String,
+    /// Primary IPv4 address, if the interface has one
+    pub v4: Option<Ipv4Addr>,
+    /// Primary IPv6 address; link-local ones are skipped (they need a
+    /// scope id and reach only the local link)
+    pub v6: Option<Ipv6Addr>,
+}
+
+impl Interface {
+    /// Primary addresses of the interface called `name` (`eth0`, `lo`)
+    pub fn lookup(name: &str) -> Result<Interface, String> {
+        let all = if_addrs::get_if_addrs().map_err(|e| format!("Cannot list interfaces: {}", e))?;
+        if !all.iter().any(|i| i.name == name) {
+            let mut names: Vec<&str> = all.iter().map(|i| i.name.as_str()).collect();
+            names.sort();
+            names.dedup();
+            return Err(format!("No interface '{}' (have: {})", name, names.join(", ")));
+        }
+
+        let addrs = all.iter().filter(|i| i.name == name).map(|i| i.ip());
+        let iface = Interface::from_addrs(name, addrs);
+        if iface.v4.is_none() && iface.v6.is_none() {
+            return Err(format!("Interface '{}' has no usable address", name));
+        }
+        Ok(iface)
+    }
+
+    /// First address of each family, in the order given
+    pub fn from_addrs(name: &str, addrs: impl IntoIterator<Item = IpAddr>) -> Interface {
+        let mut iface = Interface {
+            name: name.to_string(),
+            v4: None,
+            v6: None,
+        };
+        for ip in addrs {
+            match ip {
+                IpAddr::V4(a) => {
+                    iface.v4.get_or_insert(a);
+                }
+                IpAddr::V6(a) if !a.is_unicast_link_local() => {
+                    iface.v6.get_or_insert(a);
+                }
+                IpAddr::V6(_) => {}
+            }
+        }
+        iface
+    }
+
+    /// Local address to bind for a connect to `peer` (same family)
+    pub fn bind_addr(&self, peer: IpAddr) -> Option<IpAddr> {
+        match peer {
+            IpAddr::V4(_) => self.v4.map(IpAddr::V4),
+            IpAddr::V6(_) => self.v6.map(IpAddr::V6),
+        }
+    }
+
+    /// `TcpStream::connect_timeout`, from this interface's address
+    pub fn connect_stream(&self, addr: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+        let local = self.bind_addr(addr.ip()).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::AddrNotAvailable, "no address of the peer's family")
+        })?;
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        socket.bind(&SocketAddr::new(local, 0).into())?;
+        socket.connect_timeout(&addr.into(), timeout)?;
+        Ok(socket.into())
+    }
+}
+
+impl Connector for Interface {
+    fn connect(&self, addr: SocketAddr, timeout: Duration) -> TcpResult {
+        match self.connect_stream(addr, timeout) {
+            Ok(s) => TcpResult::Open(s.local_addr().ok()),
+            Err(e) => classify_error(&e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_an_address_per_family() {
+        let iface = Interface::from_addrs(
+            "eth0",
+            [
+                "fe80::1".parse().unwrap(),
+                "10.0.0.5".parse().unwrap(),
+                "10.0.0.6".parse().unwrap(),
+                "2001:db8::5".parse().unwrap(),
+            ],
+        );
+        assert_eq!(iface.bind_addr("192.0.2.1".parse().unwrap()), Some("10.0.0.5".parse().unwrap()));
+        assert_eq!(iface.bind_addr("2001:db8::1".parse().unwrap()), Some("2001:db8::5".parse().unwrap()));
+
+        let v4_only = Interface::from_addrs("eth1", ["10.0.0.7".parse().unwrap()]);
+        assert_eq!(v4_only.bind_addr("2001:db8::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn unknown_interface_is_an_error() {
+        let err = Interface::lookup("no-such-nic0").unwrap_err();
+        assert!(err.contains("No interface 'no-such-nic0'"), "{}", err);
+    }
+}
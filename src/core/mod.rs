@@ -3,3 +3,13 @@ pub mod target;
 pub mod ports;
 pub mod scanner;
 pub mod labels;
+pub mod services;
+pub mod report;
+pub mod proxy;
+pub mod dns;
+pub mod iface;
+pub mod tls;
+pub mod bench;
+mod top_ports;
+#[cfg(feature = "async")]
+pub mod async_scan;
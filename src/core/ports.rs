@@ -1,13 +1,85 @@
-#[derive(Clone)]
+use std::{collections::BTreeSet, fmt};
+
+use super::{services::port_for, top_ports::TOP_PORTS};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Ports {
     pub ports: Vec<u16>,
+    /// Every constructor gives TCP; see [`Ports::with_protocol`]
+    pub protocol: Protocol,
+}
+
+/// Transport the ports are scanned over
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "lowercase"))]
+pub enum Protocol {
+    #[default]
+    Tcp,
+    /// Datagrams: no reply is ambiguous, see `PortStatus::OpenFiltered`
+    Udp,
 }
 
+impl Protocol {
+    /// `tcp` / `udp` (case-insensitive)
+    pub fn parse(name: &str) -> Option<Protocol> {
+        match name.to_ascii_lowercase().as_str() {
+            "tcp" => Some(Protocol::Tcp),
+            "udp" => Some(Protocol::Udp),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+        })
+    }
+}
+
+/// Named port groups usable inside a port expression
+const GROUPS: &[(&str, &[u16])] = &[
+    ("web", &[80, 443, 8000, 8080, 8443]),
+    ("mail", &[25, 110, 143, 465, 587, 993, 995]),
+    ("db", &[1433, 1521, 3306, 5432, 6379, 27017]),
+    ("remote", &[22, 23, 3389, 5900]),
+    ("file", &[20, 21, 139, 445, 2049]),
+];
+
+/// Why a port expression was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortParseError {
+    /// Nothing left to scan (empty spec, or everything excluded)
+    Empty,
+    /// Token is neither a port, a range nor a known group name
+    InvalidToken(String),
+    /// Alphabetic token that is not a group or known service name
+    UnknownService(String),
+    /// Port outside 1–65535
+    OutOfRange(String),
+}
+
+impl fmt::Display for PortParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "port spec selects no ports"),
+            Self::InvalidToken(t) => write!(f, "invalid port token '{}'", t),
+            Self::UnknownService(t) => write!(f, "unknown service name '{}'", t),
+            Self::OutOfRange(t) => write!(f, "port out of range '{}'", t),
+        }
+    }
+}
+
+impl std::error::Error for PortParseError {}
+
 impl Ports {
     /// All ports: 1–65535
     pub fn all() -> Self {
         Self {
             ports: (1..=65535).collect(),
+            protocol: Protocol::Tcp,
         }
     }
 
@@ -15,6 +87,7 @@ impl Ports {
     pub fn single(port: u16) -> Self {
         Self {
             ports: vec![port],
+            protocol: Protocol::Tcp,
         }
     }
 
@@ -22,6 +95,7 @@ impl Ports {
     pub fn multiple(list: Vec<u16>) -> Self {
         Self {
             ports: list,
+            protocol: Protocol::Tcp,
         }
     }
 
@@ -30,6 +104,7 @@ impl Ports {
         let (a, b) = if start <= end { (start, end) } else { (end, start) };
         Self {
             ports: (a..=b).collect(),
+            protocol: Protocol::Tcp,
         }
     }
 
@@ -43,6 +118,314 @@ impl Ports {
                 3389, 5432, 6379,
                 8080, 8443,
             ],
+            protocol: Protocol::Tcp,
+        }
+    }
+
+    /// nmap's default set: the 1000 most frequently open ports
+    pub fn top_1000() -> Self {
+        Self::top(TOP_PORTS.len())
+    }
+
+    /// The `n` most frequently open ports (nmap ordering).
+    /// `n` is clamped to the embedded list length (1000).
+    pub fn top(n: usize) -> Self {
+        let n = n.min(TOP_PORTS.len());
+        Self {
+            ports: TOP_PORTS[..n].to_vec(),
+            protocol: Protocol::Tcp,
+        }
+    }
+
+    /// Ports of well-known service names (`ssh`, `HTTP`), sorted and
+    /// deduplicated; `None` if any name is unknown or none is given
+    pub fn from_service_names(names: &[&str]) -> Option<Self> {
+        let ports = names
+            .iter()
+            .map(|n| port_for(n.trim()))
+            .collect::<Option<BTreeSet<u16>>>()?;
+        if ports.is_empty() {
+            return None;
+        }
+        Some(Self::multiple(ports.into_iter().collect()))
+    }
+
+    /// Drop `others` from the set; ports not in it are ignored
+    pub fn exclude(&mut self, others: &[u16]) {
+        let others: BTreeSet<u16> = others.iter().copied().collect();
+        self.ports.retain(|p| !others.contains(p));
+    }
+
+    /// The same ports over `protocol`
+    pub fn with_protocol(self, protocol: Protocol) -> Self {
+        Self { protocol, ..self }
+    }
+
+    /// Parse a port expression.
+    ///
+    /// Grammar (whitespace around items is ignored):
+    ///
+    /// ```text
+    /// spec  := item ("," item)*
+    /// item  := "!"? atom
+    /// atom  := port | port ("-" | ":") port | group | service | "top" n | "all" | "-"
+    /// group := web | mail | db | remote | file
+    /// ```
+    ///
+    /// `all` and a lone `-` both mean 1–65535. A `service` is any name
+    /// from the `services` table (`ssh` → 22, `http` → 80, ...); names
+    /// are case-insensitive.
+    ///
+    /// Ranges may be written in either order, with `-` or (as some other
+    /// tools do) `:` between the ends. `!` items are subtracted
+    /// after everything else is collected, so their position does not
+    /// matter. The result is deduplicated and sorted.
+    ///
+    /// Example: `web,8000-8100,!8080`
+    pub fn parse(spec: &str) -> Result<Self, PortParseError> {
+        let mut include = BTreeSet::new();
+        let mut exclude = BTreeSet::new();
+
+        for item in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let (set, atom) = match item.strip_prefix('!') {
+                Some(rest) => (&mut exclude, rest.trim()),
+                None => (&mut include, item),
+            };
+            set.extend(parse_atom(atom)?);
+        }
+
+        let ports: Vec<u16> = include.difference(&exclude).copied().collect();
+        if ports.is_empty() {
+            return Err(PortParseError::Empty);
+        }
+
+        Ok(Self {
+            ports,
+            protocol: Protocol::Tcp,
+        })
+    }
+}
+
+/// Sorted ports collapsed into inclusive ranges: 1,2,3,8080 →
+/// (1,3),(8080,8080)
+pub fn ranges(sorted: impl IntoIterator<Item = u16>) -> Vec<(u16, u16)> {
+    let mut out: Vec<(u16, u16)> = Vec::new();
+    for port in sorted {
+        match out.last_mut() {
+            Some((_, end)) if end.checked_add(1) == Some(port) => *end = port,
+            _ => out.push((port, port)),
+        }
+    }
+    out
+}
+
+// =======================
+// GRAMMAR HELPERS
+// =======================
+fn parse_atom(atom: &str) -> Result<Vec<u16>, PortParseError> {
+    if atom == "-" || atom.eq_ignore_ascii_case("all") {
+        return Ok(Ports::all().ports);
+    }
+
+    if let Some((_, ports)) = GROUPS.iter().find(|(name, _)| name.eq_ignore_ascii_case(atom)) {
+        return Ok(ports.to_vec());
+    }
+
+    if atom.get(..3).is_some_and(|p| p.eq_ignore_ascii_case("top")) {
+        return match atom[3..].parse::<usize>() {
+            Ok(n) if n > 0 => Ok(Ports::top(n).ports),
+            _ => Err(PortParseError::InvalidToken(atom.to_string())),
+        };
+    }
+
+    let is_name = atom.starts_with(|c: char| c.is_ascii_alphabetic())
+        && atom.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+
+    if is_name {
+        return match port_for(atom) {
+            Some(p) => Ok(vec![p]),
+            None => Err(PortParseError::UnknownService(atom.to_string())),
+        };
+    }
+
+    if let Some((a, b)) = atom.split_once(['-', ':']) {
+        let r = Ports::range(parse_port(a.trim())?, parse_port(b.trim())?);
+        return Ok(r.ports);
+    }
+
+    Ok(vec![parse_port(atom)?])
+}
+
+fn parse_port(raw: &str) -> Result<u16, PortParseError> {
+    if raw.is_empty() || !raw.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(PortParseError::InvalidToken(raw.to_string()));
+    }
+
+    match raw.parse::<u32>() {
+        Ok(p @ 1..=65535) => Ok(p as u16),
+        _ => Err(PortParseError::OutOfRange(raw.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(spec: &str) -> Vec<u16> {
+        Ports::parse(spec).unwrap().ports
+    }
+
+    #[test]
+    fn single_list_and_range() {
+        assert_eq!(parse("80"), vec![80]);
+        assert_eq!(parse("443,22,80"), vec![22, 80, 443]);
+        assert_eq!(parse("20-23"), vec![20, 21, 22, 23]);
+        assert_eq!(parse("23-20"), vec![20, 21, 22, 23]);
+    }
+
+    #[test]
+    fn colon_range_separator() {
+        assert_eq!(parse("20:23"), vec![20, 21, 22, 23]);
+        assert_eq!(parse("25:22,80"), parse("22-25,80"));
+        assert_eq!(parse("web,!8000:8443"), vec![80, 443]);
+        assert!(Ports::parse("80:").is_err());
+        assert!(Ports::parse("1:2:3").is_err());
+    }
+
+    #[test]
+    fn mixed_tokens_are_deduplicated_and_sorted() {
+        assert_eq!(parse("25, 20-22 ,21,80"), vec![20, 21, 22, 25, 80]);
+        assert_eq!(parse("10-12,11-13"), vec![10, 11, 12, 13]);
+
+        let nmap_style = parse("22,80,100-200,443");
+        assert_eq!(nmap_style.len(), 2 + 101 + 1);
+        assert_eq!((nmap_style[1], nmap_style[2], nmap_style[103]), (80, 100, 443));
+    }
+
+    #[test]
+    fn groups_are_case_insensitive() {
+        assert_eq!(parse("web"), vec![80, 443, 8000, 8080, 8443]);
+        assert_eq!(parse("WEB"), parse("web"));
+    }
+
+    #[test]
+    fn group_range_and_exclusion_combine() {
+        let ports = parse("web,8000-8100,!8080");
+        assert!(ports.contains(&80));
+        assert!(ports.contains(&443));
+        assert!(ports.contains(&8000));
+        assert!(ports.contains(&8100));
+        assert!(ports.contains(&8443));
+        assert!(!ports.contains(&8080));
+        // 80, 443, 8443 + 8000..=8100 minus 8080
+        assert_eq!(ports.len(), 3 + 101 - 1);
+    }
+
+    #[test]
+    fn exclusion_is_order_independent() {
+        assert_eq!(parse("!80,79-81"), parse("79-81,!80"));
+        assert_eq!(parse("1-10,!web,!2-9"), vec![1, 10]);
+        assert_eq!(parse("22,!443"), vec![22]);
+    }
+
+    #[test]
+    fn exclude_subtracts_present_ports_only() {
+        let mut all = Ports::all();
+        all.exclude(&[80]);
+        assert_eq!(all.ports.len(), 65534);
+        assert!(!all.ports.contains(&80));
+
+        let mut web = Ports::parse("web").unwrap();
+        web.exclude(&[8443, 22, 80, 22]);
+        assert_eq!(web.ports, vec![443, 8000, 8080]);
+    }
+
+    #[test]
+    fn top_is_frequency_ordered_and_clamped() {
+        assert_eq!(Ports::top(3).ports, vec![80, 23, 443]);
+        assert_eq!(Ports::top(200).ports.len(), 200);
+        assert_eq!(Ports::top(5000).ports.len(), 1000);
+        assert!(Ports::top(0).ports.is_empty());
+    }
+
+    #[test]
+    fn top_1000_matches_nmap_defaults() {
+        let ports = Ports::top_1000().ports;
+        assert_eq!(ports.iter().collect::<BTreeSet<_>>().len(), 1000);
+        for p in [22, 80, 443, 3389] {
+            assert!(ports.contains(&p), "{}", p);
         }
     }
+
+    #[test]
+    fn top_keyword() {
+        assert_eq!(parse("top3"), vec![23, 80, 443]);
+        assert_eq!(parse("top1000").len(), 1000);
+        assert_eq!(parse("top2,!80"), vec![23]);
+        assert_eq!(
+            Ports::parse("top"),
+            Err(PortParseError::InvalidToken("top".into()))
+        );
+    }
+
+    #[test]
+    fn service_names() {
+        assert_eq!(parse("ssh,http,https"), vec![22, 80, 443]);
+        assert_eq!(parse("SSH,Ftp"), vec![21, 22]);
+        assert_eq!(parse("http-alt,22-23"), vec![22, 23, 8080]);
+        assert_eq!(parse("web,!https"), vec![80, 8000, 8080, 8443]);
+    }
+
+    #[test]
+    fn ports_from_service_names() {
+        let ports = |names: &[&str]| Ports::from_service_names(names).map(|p| p.ports);
+        assert_eq!(ports(&["https", "ssh", "http"]), Some(vec![22, 80, 443]));
+        assert_eq!(ports(&["SSH"]), ports(&["ssh"]));
+        assert_eq!(ports(&["ssh", "Ssh"]), Some(vec![22]));
+        assert_eq!(ports(&["ssh", "gopher"]), None);
+        assert_eq!(ports(&[]), None);
+        // Names and numbers mix freely in a port expression
+        assert_eq!(parse("SSH,8000,https,21-22"), vec![21, 22, 443, 8000]);
+    }
+
+    #[test]
+    fn all_keyword() {
+        assert_eq!(Ports::parse("all"), Ok(Ports::all()));
+        assert_eq!(Ports::parse("-"), Ok(Ports::all()));
+        assert_eq!(Ports::parse("1-65535"), Ok(Ports::all()));
+        assert_eq!(parse("all,!80").len(), 65534);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(Ports::parse(""), Err(PortParseError::Empty));
+        assert_eq!(Ports::parse("80,!80"), Err(PortParseError::Empty));
+        assert_eq!(Ports::parse("!80"), Err(PortParseError::Empty));
+        assert_eq!(
+            Ports::parse("22,abc"),
+            Err(PortParseError::UnknownService("abc".into()))
+        );
+        assert_eq!(
+            Ports::parse("8o"),
+            Err(PortParseError::InvalidToken("8o".into()))
+        );
+        assert_eq!(
+            Ports::parse("1-"),
+            Err(PortParseError::InvalidToken("".into()))
+        );
+        assert_eq!(
+            Ports::parse("70000"),
+            Err(PortParseError::OutOfRange("70000".into()))
+        );
+        assert_eq!(
+            Ports::parse("0-10"),
+            Err(PortParseError::OutOfRange("0".into()))
+        );
+    }
+
+    #[test]
+    fn collapses_ranges() {
+        assert_eq!(ranges([1, 2, 3, 8080, 65534, 65535]), vec![(1, 3), (8080, 8080), (65534, 65535)]);
+        assert!(ranges([]).is_empty());
+    }
 }
@@ -0,0 +1,73 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::core::scanner::{Connector, TcpResult};
+
+/// Proxy the scan tunnels its connects through
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Proxy {
+    /// HTTP proxy speaking `CONNECT host:port`
+    Http(SocketAddr),
+}
+
+impl Proxy {
+    /// `http://host:port`
+    pub fn parse(spec: &str) -> Result<Proxy, String> {
+        let Some(rest) = spec.strip_prefix("http://") else {
+            return Err(format!("Unsupported proxy '{}' (expected http://host:port)", spec));
+        };
+        let rest = rest.trim_end_matches('/');
+
+        rest.to_socket_addrs()
+            .ok()
+            .and_then(|mut a| a.next())
+            .map(Proxy::Http)
+            .ok_or_else(|| format!("Cannot resolve proxy '{}'", rest))
+    }
+}
+
+impl Connector for Proxy {
+    fn connect(&self, addr: SocketAddr, timeout: Duration) -> TcpResult {
+        match self {
+            Proxy::Http(proxy) => http_connect(*proxy, addr, timeout),
+        }
+    }
+}
+
+// =======================
+// HTTP CONNECT
+// =======================
+fn http_connect(proxy: SocketAddr, target: SocketAddr, timeout: Duration) -> TcpResult {
+    // Proxy itself unreachable: no answer about the target either
+    let Ok(mut s) = TcpStream::connect_timeout(&proxy, timeout) else {
+        return TcpResult::Timeout;
+    };
+    s.set_read_timeout(Some(timeout)).ok();
+    s.set_write_timeout(Some(timeout)).ok();
+
+    let req = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", target);
+    if s.write_all(req.as_bytes()).is_err() {
+        return TcpResult::Timeout;
+    }
+
+    // "HTTP/1.1 200 Connection established"
+    let mut line = String::new();
+    match BufReader::new(&s).read_line(&mut line) {
+        Ok(n) if n > 0 => {}
+        _ => return TcpResult::Timeout,
+    }
+    let code = line.split_whitespace().nth(1).and_then(|c| c.parse::<u16>().ok());
+
+    match code {
+        Some(200) => TcpResult::Open(s.local_addr().ok()),
+        // The proxy tried the target and got refused / no answer
+        Some(502 | 503) => TcpResult::Refused,
+        Some(504) => TcpResult::Timeout,
+        // 403, 407, ...: the proxy said no, the target was never asked
+        Some(c) => TcpResult::ProxyDenied(c),
+        None => TcpResult::ProxyDenied(0),
+    }
+}
@@ -0,0 +1,680 @@
+//! Result exports (JSON, CSV, nmap XML) and saved reports.
+//!
+//! JSON is written and read by hand: `serde` is an optional feature
+//! (derives only) and serde_json is not a dependency, yet `save` /
+//! `load` must work in a default build. The reader only needs the
+//! shapes [`report_json`] writes, so it stays small.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::core::{
+    engine::{ScanMeta, ScanReport},
+    ports::{self, Protocol},
+    scanner::{PortStatus, ScanResult, NO_SERVICE},
+    tls::TlsInfo,
+};
+
+// =======================
+// JSON
+// =======================
+/// One result as a compact JSON object (also one NDJSON line)
+pub fn result_json(r: &ScanResult) -> String {
+    let fields: Vec<String> = result_fields(r).iter().map(|(k, v)| format!("\"{}\":{}", k, v)).collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+/// All results wrapped as `{"results":[...]}`
+pub fn to_json(results: &[ScanResult]) -> String {
+    let items: Vec<String> = results.iter().map(result_json).collect();
+    format!("{{\"results\":[{}]}}", items.join(","))
+}
+
+/// Results as one indented JSON array, for reading rather than piping
+pub fn results_json_pretty(results: &[ScanResult]) -> String {
+    if results.is_empty() {
+        return "[]".into();
+    }
+    let items: Vec<String> = results.iter().map(|r| format!("  {}", pretty_object(r, "  "))).collect();
+    format!("[\n{}\n]", items.join(",\n"))
+}
+
+/// [`to_json`], indented
+pub fn to_json_pretty(results: &[ScanResult]) -> String {
+    if results.is_empty() {
+        return "{\n  \"results\": []\n}".into();
+    }
+    let items: Vec<String> = results.iter().map(|r| format!("    {}", pretty_object(r, "    "))).collect();
+    format!("{{\n  \"results\": [\n{}\n  ]\n}}", items.join(",\n"))
+}
+
+/// Key order is the same in every output format
+fn result_fields(r: &ScanResult) -> [(&'static str, String); 13] {
+    [
+        ("host", json_str(&r.host)),
+        ("port", r.port.to_string()),
+        ("protocol", format!("\"{}\"", r.protocol)),
+        ("status", format!("\"{}\"", r.status)),
+        ("service", json_str(&r.service)),
+        ("os", json_opt(r.os_hint)),
+        ("confidence", r.confidence.to_string()),
+        ("detail", json_opt(r.detail.as_deref())),
+        ("addr", json_opt(r.remote_addr.map(|a| a.to_string()).as_deref())),
+        ("local", json_opt(r.local_addr.map(|a| a.to_string()).as_deref())),
+        ("banner", json_opt(r.banner.as_deref())),
+        ("latency_ms", r.latency.map_or("null".into(), |d| format!("{:.3}", d.as_secs_f64() * 1000.0))),
+        ("tls", r.tls_info.as_ref().map_or("null".into(), tls_json)),
+    ]
+}
+
+/// Handshake details as one inline object, also in the pretty output
+fn tls_json(tls: &TlsInfo) -> String {
+    let san: Vec<String> = tls.san.iter().map(|s| json_str(s)).collect();
+    format!(
+        "{{\"protocol\":{},\"alpn\":{},\"common_name\":{},\"san\":[{}],\"not_after\":{}}}",
+        json_str(&tls.protocol),
+        json_opt(tls.alpn.as_deref()),
+        json_opt(tls.common_name.as_deref()),
+        san.join(","),
+        json_opt(tls.not_after.as_deref()),
+    )
+}
+
+/// Object with one field per line; `indent` is where its braces sit
+fn pretty_object(r: &ScanResult, indent: &str) -> String {
+    let fields: Vec<String> = result_fields(r)
+        .iter()
+        .map(|(k, v)| format!("{}  \"{}\": {}", indent, k, v))
+        .collect();
+    format!("{{\n{}\n{}}}", fields.join(",\n"), indent)
+}
+
+fn json_opt(v: Option<&str>) -> String {
+    v.map(json_str).unwrap_or_else(|| "null".into())
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// =======================
+// SAVED REPORT
+// =======================
+/// Every [`ScanResult::os_hint`] the scanner sets; a loaded name must
+/// be one of them to stay a `&'static str`, others are dropped
+const OS_HINTS: &[&str] = &["Windows", "Unix-like"];
+
+/// Nesting deeper than this is not a report (and would only eat stack)
+const MAX_DEPTH: usize = 64;
+
+/// A whole [`ScanReport`] for `save`: what was scanned and when, then
+/// one [`result_json`] object per line. [`parse_report`] reads it back.
+pub fn report_json(report: &ScanReport) -> String {
+    let items: Vec<String> = report.results.iter().map(|r| format!("    {}", result_json(r))).collect();
+    let results = match items.is_empty() {
+        true => "[]".to_string(),
+        false => format!("[\n{}\n  ]", items.join(",\n")),
+    };
+    format!(
+        "{{\n  \"target\": {},\n  \"started_at\": \"{}\",\n  \"duration_ms\": {},\n  \"results\": {}\n}}\n",
+        json_str(&report.target),
+        report.started_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+        report.duration.as_millis(),
+        results
+    )
+}
+
+/// Why a saved report could not be read back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// Not JSON; `at` is the byte offset of the problem
+    Syntax { at: usize, reason: &'static str },
+    /// JSON, but a field is missing or holds the wrong kind of value;
+    /// `result` is the index of the result it belongs to
+    Field { result: Option<usize>, field: &'static str },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Syntax { at, reason } => write!(f, "not valid JSON at byte {}: {}", at, reason),
+            LoadError::Field { result: Some(i), field } => {
+                write!(f, "result {}: missing or invalid \"{}\"", i + 1, field)
+            }
+            LoadError::Field { result: None, field } => write!(f, "missing or invalid \"{}\"", field),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Read back what [`report_json`] wrote. Every field is checked, so a
+/// truncated or hand-edited file is an error, not a half-filled report.
+pub fn parse_report(text: &str) -> Result<ScanReport, LoadError> {
+    let root = Json::parse(text)?;
+    let missing = |field| LoadError::Field { result: None, field };
+
+    let target = root.get("target").and_then(Json::as_str).ok_or(missing("target"))?;
+    let started_at = root
+        .get("started_at")
+        .and_then(Json::as_str)
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .ok_or(missing("started_at"))?;
+    let duration = root.get("duration_ms").and_then(Json::as_u64).ok_or(missing("duration_ms"))?;
+    let results = root
+        .get("results")
+        .and_then(Json::as_array)
+        .ok_or(missing("results"))?
+        .iter()
+        .enumerate()
+        .map(|(i, item)| parse_result(item).map_err(|field| LoadError::Field { result: Some(i), field }))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ScanReport {
+        target: target.to_string(),
+        started_at: started_at.with_timezone(&Utc),
+        duration: Duration::from_millis(duration),
+        results,
+        // Not saved: they explained the original scan's gaps
+        errors: Vec::new(),
+    })
+}
+
+/// One [`result_fields`] object; `Err` names the first bad field. Every
+/// key must be there, `null` where the value is unset.
+fn parse_result(item: &Json) -> Result<ScanResult, &'static str> {
+    let field = |key: &'static str| item.get(key).ok_or(key);
+    let text = |key: &'static str| match field(key)? {
+        Json::Null => Ok(None),
+        Json::Str(s) => Ok(Some(s.clone())),
+        _ => Err(key),
+    };
+    let addr = |key: &'static str| -> Result<Option<SocketAddr>, &'static str> {
+        text(key)?.map(|a| a.parse().map_err(|_| key)).transpose()
+    };
+
+    Ok(ScanResult {
+        host: field("host")?.as_str().ok_or("host")?.to_string(),
+        port: field("port")?.as_u64().and_then(|p| u16::try_from(p).ok()).ok_or("port")?,
+        protocol: field("protocol")?.as_str().and_then(Protocol::parse).ok_or("protocol")?,
+        status: field("status")?.as_str().and_then(PortStatus::parse).ok_or("status")?,
+        service: field("service")?.as_str().ok_or("service")?.to_string(),
+        os_hint: text("os")?.and_then(|os| OS_HINTS.iter().copied().find(|h| *h == os)),
+        confidence: field("confidence")?.as_u64().and_then(|c| u8::try_from(c).ok()).ok_or("confidence")?,
+        detail: text("detail")?,
+        remote_addr: addr("addr")?,
+        local_addr: addr("local")?,
+        banner: text("banner")?,
+        latency: match field("latency_ms")? {
+            Json::Null => None,
+            Json::Num(ms) if ms.is_finite() && *ms >= 0.0 => Some(Duration::from_secs_f64(ms / 1000.0)),
+            _ => return Err("latency_ms"),
+        },
+        tls_info: match field("tls")? {
+            Json::Null => None,
+            tls => Some(parse_tls(tls).ok_or("tls")?),
+        },
+    })
+}
+
+/// The [`tls_json`] object
+fn parse_tls(tls: &Json) -> Option<TlsInfo> {
+    let text = |key: &str| -> Option<Option<String>> {
+        match tls.get(key)? {
+            Json::Null => Some(None),
+            Json::Str(s) => Some(Some(s.clone())),
+            _ => None,
+        }
+    };
+    Some(TlsInfo {
+        protocol: tls.get("protocol")?.as_str()?.to_string(),
+        alpn: text("alpn")?,
+        common_name: text("common_name")?,
+        san: tls
+            .get("san")?
+            .as_array()?
+            .iter()
+            .map(|s| s.as_str().map(str::to_string))
+            .collect::<Option<_>>()?,
+        not_after: text("not_after")?,
+    })
+}
+
+/// Just enough JSON to read a saved report back
+#[derive(Debug)]
+enum Json {
+    Null,
+    /// `true` / `false`: no report field is a boolean, so only its
+    /// place is kept
+    Bool,
+    Num(f64),
+    Str(String),
+    Arr(Vec<Json>),
+    /// Keys in file order
+    Obj(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// One value, with nothing but whitespace after it
+    fn parse(text: &str) -> Result<Json, LoadError> {
+        let mut parser = JsonParser { text, at: 0, depth: 0 };
+        let value = parser.value()?;
+        parser.skip_ws();
+        match parser.at == text.len() {
+            true => Ok(value),
+            false => Err(parser.error("trailing characters")),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Obj(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Whole, non-negative numbers only
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Num(n) if n.fract() == 0.0 && (0.0..=u64::MAX as f64).contains(n) => Some(*n as u64),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Arr(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    text: &'a str,
+    /// Byte offset of the next character
+    at: usize,
+    /// Arrays / objects open around `at`
+    depth: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self, reason: &'static str) -> LoadError {
+        LoadError::Syntax { at: self.at, reason }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.at).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while self.peek().is_some_and(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r')) {
+            self.at += 1;
+        }
+    }
+
+    /// Skips whitespace, then `byte` if it comes next
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_ws();
+        let found = self.peek() == Some(byte);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn value(&mut self) -> Result<Json, LoadError> {
+        self.skip_ws();
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some(b'{' | b'[') if self.depth == MAX_DEPTH => Err(self.error("nested too deep")),
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Json::Str),
+            Some(b't') => self.literal("true", Json::Bool),
+            Some(b'f') => self.literal("false", Json::Bool),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, LoadError> {
+        match self.text[self.at..].starts_with(word) {
+            true => {
+                self.at += word.len();
+                Ok(value)
+            }
+            false => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, LoadError> {
+        let start = self.at;
+        while self.peek().is_some_and(|b| matches!(b, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+            self.at += 1;
+        }
+        self.text[start..self.at]
+            .parse()
+            .map(Json::Num)
+            .map_err(|_| LoadError::Syntax { at: start, reason: "invalid number" })
+    }
+
+    /// `at` is on the opening quote
+    fn string(&mut self) -> Result<String, LoadError> {
+        self.at += 1;
+        let mut out = String::new();
+        loop {
+            // Runs end on ASCII bytes, so they are whole characters
+            let start = self.at;
+            while self.peek().is_some_and(|b| b != b'"' && b != b'\\' && b >= 0x20) {
+                self.at += 1;
+            }
+            out.push_str(&self.text[start..self.at]);
+            match self.peek() {
+                Some(b'"') => {
+                    self.at += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.at += 1;
+                    out.push(self.escape()?);
+                }
+                Some(_) => return Err(self.error("control character in string")),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    /// Character spelled by the escape after a backslash
+    fn escape(&mut self) -> Result<char, LoadError> {
+        let Some(byte) = self.peek() else {
+            return Err(self.error("unterminated string"));
+        };
+        self.at += 1;
+        Ok(match byte {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'/' => '/',
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => {
+                let mut code = self.hex4()?;
+                // A surrogate pair spells one character beyond U+FFFF
+                if (0xD800..0xDC00).contains(&code) && self.text[self.at..].starts_with("\\u") {
+                    self.at += 2;
+                    let low = self.hex4()?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error("invalid surrogate pair"));
+                    }
+                    code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                }
+                char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"))?
+            }
+            _ => return Err(LoadError::Syntax { at: self.at - 1, reason: "invalid escape" }),
+        })
+    }
+
+    fn hex4(&mut self) -> Result<u32, LoadError> {
+        let code = self
+            .text
+            .get(self.at..self.at + 4)
+            .filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|h| u32::from_str_radix(h, 16).ok())
+            .ok_or_else(|| self.error("invalid \\u escape"))?;
+        self.at += 4;
+        Ok(code)
+    }
+
+    fn array(&mut self) -> Result<Json, LoadError> {
+        self.at += 1;
+        self.depth += 1;
+        let mut items = Vec::new();
+        if !self.eat(b']') {
+            loop {
+                items.push(self.value()?);
+                if self.eat(b']') {
+                    break;
+                }
+                if !self.eat(b',') {
+                    return Err(self.error("expected ',' or ']'"));
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(Json::Arr(items))
+    }
+
+    fn object(&mut self) -> Result<Json, LoadError> {
+        self.at += 1;
+        self.depth += 1;
+        let mut fields = Vec::new();
+        if !self.eat(b'}') {
+            loop {
+                self.skip_ws();
+                if self.peek() != Some(b'"') {
+                    return Err(self.error("expected a key"));
+                }
+                let key = self.string()?;
+                if !self.eat(b':') {
+                    return Err(self.error("expected ':'"));
+                }
+                fields.push((key, self.value()?));
+                if self.eat(b'}') {
+                    break;
+                }
+                if !self.eat(b',') {
+                    return Err(self.error("expected ',' or '}'"));
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(Json::Obj(fields))
+    }
+}
+
+// =======================
+// CSV
+// =======================
+/// `port,status,service` header plus one line per result, closed and
+/// filtered ports included. UDP ports read `53/udp`.
+pub fn to_csv(results: &[ScanResult]) -> String {
+    let mut out = String::from("port,status,service\n");
+    for r in results {
+        let row = [r.port_label(), r.status.to_string(), r.service.clone()];
+        let row: Vec<String> = row.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Quoted (RFC 4180) when it holds a comma, quote or line break
+fn csv_field(s: &str) -> String {
+    match s.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.to_string(),
+    }
+}
+
+// =======================
+// NMAP XML
+// =======================
+/// Minimal nmap-style `<nmaprun>` document (connect or UDP scan, XML
+/// output 1.05), for tools that ingest nmap XML. Skipped ports are left
+/// out; a port the proxy refused is `filtered`.
+pub fn to_nmap_xml(results: &[ScanResult], meta: &ScanMeta) -> String {
+    let start = meta.started_at.timestamp();
+    let (kind, protocol) = match results.first().map(|r| r.protocol) {
+        Some(Protocol::Udp) => ("udp", "udp"),
+        _ => ("connect", "tcp"),
+    };
+    let services: BTreeSet<u16> = results.iter().map(|r| r.port).collect();
+    let ranges: Vec<String> = ports::ranges(services.iter().copied())
+        .into_iter()
+        .map(|(a, b)| if a == b { a.to_string() } else { format!("{}-{}", a, b) })
+        .collect();
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE nmaprun>\n");
+    out.push_str(&format!(
+        "<nmaprun scanner=\"wise1738\" args=\"{}\" start=\"{}\" startstr=\"{}\" version=\"{}\" xmloutputversion=\"1.05\">\n",
+        xml_attr(&format!("wise1738 {}", meta.target)),
+        start,
+        meta.started_at.format("%a %b %e %H:%M:%S %Y"),
+        env!("CARGO_PKG_VERSION"),
+    ));
+    out.push_str(&format!(
+        "<scaninfo type=\"{}\" protocol=\"{}\" numservices=\"{}\" services=\"{}\"/>\n",
+        kind,
+        protocol,
+        services.len(),
+        ranges.join(","),
+    ));
+    out.push_str("<verbose level=\"0\"/>\n<debugging level=\"0\"/>\n");
+
+    // Hosts in first-seen order
+    let mut hosts: Vec<&str> = Vec::new();
+    for r in results {
+        if !hosts.contains(&r.host.as_str()) {
+            hosts.push(&r.host);
+        }
+    }
+    let mut up = 0;
+    for host in &hosts {
+        let ports: Vec<&ScanResult> = results.iter().filter(|r| r.host == *host).collect();
+        let answered = ports.iter().any(|r| matches!(r.status, PortStatus::Open | PortStatus::Closed));
+        up += answered as usize;
+        out.push_str(&nmap_host(host, &ports, answered, start));
+    }
+
+    let end = start + (meta.duration_ms / 1000) as i64;
+    out.push_str(&format!(
+        "<runstats><finished time=\"{}\" elapsed=\"{:.2}\" exit=\"success\"/><hosts up=\"{}\" down=\"{}\" total=\"{}\"/></runstats>\n</nmaprun>\n",
+        end,
+        meta.duration_ms as f64 / 1000.0,
+        up,
+        hosts.len() - up,
+        hosts.len(),
+    ));
+    out
+}
+
+/// One `<host>`: the address comes from the host itself when it is an
+/// IP, otherwise from whichever address accepted a connection
+fn nmap_host(host: &str, ports: &[&ScanResult], up: bool, start: i64) -> String {
+    let ip = host
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| ports.iter().find_map(|r| r.remote_addr.map(|a| a.ip())));
+
+    let mut out = format!("<host starttime=\"{}\">", start);
+    match up {
+        true => out.push_str("<status state=\"up\" reason=\"user-set\" reason_ttl=\"0\"/>"),
+        false => out.push_str("<status state=\"down\" reason=\"no-response\" reason_ttl=\"0\"/>"),
+    }
+    if let Some(ip) = ip {
+        let kind = if ip.is_ipv4() { "ipv4" } else { "ipv6" };
+        out.push_str(&format!("\n<address addr=\"{}\" addrtype=\"{}\"/>", ip, kind));
+    }
+    if ip.is_none_or(|ip| ip.to_string() != host) {
+        out.push_str(&format!("\n<hostnames><hostname name=\"{}\" type=\"user\"/></hostnames>", xml_attr(host)));
+    }
+
+    out.push_str("\n<ports>");
+    for r in ports {
+        let (state, reason) = match (r.status, r.protocol) {
+            (PortStatus::Open, Protocol::Tcp) => ("open", "syn-ack"),
+            (PortStatus::Open, Protocol::Udp) => ("open", "udp-response"),
+            (PortStatus::Closed, Protocol::Tcp) => ("closed", "conn-refused"),
+            (PortStatus::Closed, Protocol::Udp) => ("closed", "port-unreach"),
+            (PortStatus::Filtered | PortStatus::ProxyDenied, _) => ("filtered", "no-response"),
+            (PortStatus::OpenFiltered, _) => ("open|filtered", "no-response"),
+            (PortStatus::Unreachable, _) => ("filtered", "host-unreach"),
+            (PortStatus::Skipped, _) => continue,
+        };
+        out.push_str(&format!(
+            "\n<port protocol=\"{}\" portid=\"{}\"><state state=\"{}\" reason=\"{}\" reason_ttl=\"0\"/>",
+            r.protocol, r.port, state, reason
+        ));
+        out.push_str(&nmap_service(r));
+        out.push_str("</port>");
+    }
+    out.push_str("\n</ports>\n</host>\n");
+    out
+}
+
+/// `<service>` in nmap's vocabulary; a probe-confirmed service on an
+/// open port is `probed`, the rest is the port table's guess
+fn nmap_service(r: &ScanResult) -> String {
+    // An SSH server's identification line (`SSH-2.0-OpenSSH_9.6`)
+    let ssh_id = r.service.starts_with("SSH-").then_some(r.service.as_str());
+    let name = match r.service.as_str() {
+        _ if ssh_id.is_some() => "ssh".to_string(),
+        "unknown" | NO_SERVICE => return String::new(),
+        "DNS" => "domain".to_string(),
+        "SMB" => "microsoft-ds".to_string(),
+        "RDP" => "ms-wbt-server".to_string(),
+        "POSTGRES" => "postgresql".to_string(),
+        "HTTP-ALT" => "http-proxy".to_string(),
+        "MSSQL" => "ms-sql-s".to_string(),
+        "ORACLE" => "oracle-tns".to_string(),
+        "RSH" => "shell".to_string(),
+        "SQUID" => "squid-http".to_string(),
+        "WINRM" => "wsman".to_string(),
+        s => s.to_ascii_lowercase(),
+    };
+    let (method, conf) = match r.status == PortStatus::Open && r.confidence >= 85 {
+        true => ("probed", 10),
+        false => ("table", 3),
+    };
+    let extra = r
+        .detail
+        .as_deref()
+        .or(ssh_id)
+        .map(|d| format!(" extrainfo=\"{}\"", xml_attr(d)))
+        .unwrap_or_default();
+    format!("<service name=\"{}\"{} method=\"{}\" conf=\"{}\"/>", name, extra, method, conf)
+}
+
+fn xml_attr(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' | '\n' | '\r' => out.push_str(&format!("&#{};", c as u32)),
+            // Other control characters are not allowed in XML 1.0 at all
+            c if (c as u32) < 0x20 => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -1,293 +1,2954 @@
 use std::{
-    io::{Read, Write},
-    net::{SocketAddr, TcpStream, ToSocketAddrs},
-    sync::mpsc,
+    collections::BTreeSet,
+    io::{BufRead, BufReader, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::core::{
-    ports::Ports,
+    dns,
+    iface::Interface,
+    ports::{Ports, Protocol},
+    proxy::Proxy,
+    services::{service_for, udp_service_for},
     target::Target,
+    tls::TlsInfo,
 };
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum PortStatus {
     Open,
     Closed,
     Filtered,
+    /// Not probed: the host's time budget ran out first
+    Skipped,
+    /// The proxy refused the tunnel; the target port was never tried
+    ProxyDenied,
+    /// UDP port that sent nothing back: a silent service and a firewall
+    /// that drops the datagram look the same
+    OpenFiltered,
+    /// No route to the host or its network; the port was never reached
+    Unreachable,
 }
 
-#[derive(Debug)]
+/// `Open|Filtered` for [`PortStatus::OpenFiltered`], the variant name
+/// for the rest
+impl std::fmt::Display for PortStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PortStatus::OpenFiltered => f.write_str("Open|Filtered"),
+            status => write!(f, "{:?}", status),
+        }
+    }
+}
+
+impl PortStatus {
+    /// Inverse of the `Display` text: `open`, `Open|Filtered`
+    /// (case-insensitive)
+    pub fn parse(name: &str) -> Option<PortStatus> {
+        match name.to_ascii_lowercase().as_str() {
+            "open" => Some(PortStatus::Open),
+            "closed" => Some(PortStatus::Closed),
+            "filtered" => Some(PortStatus::Filtered),
+            "skipped" => Some(PortStatus::Skipped),
+            "proxydenied" => Some(PortStatus::ProxyDenied),
+            "open|filtered" => Some(PortStatus::OpenFiltered),
+            "unreachable" => Some(PortStatus::Unreachable),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ScanResult {
+    pub host: String,
     pub port: u16,
+    pub protocol: Protocol,
     pub status: PortStatus,
-    pub service: &'static str,          
+    /// Probe-confirmed name, the server's own identification when it
+    /// sends one (`SSH-2.0-OpenSSH_9.6`), or the port table's guess
+    pub service: String,
     pub os_hint: Option<&'static str>,  
     pub confidence: u8,                 
+    /// What the probe learned beyond the name (banner, version, ...)
+    pub detail: Option<String>,
+    /// Address that accepted the connection (open ports only)
+    pub remote_addr: Option<SocketAddr>,
+    /// Local end of the connection (verbose scans only)
+    pub local_addr: Option<SocketAddr>,
+    /// Printable start of what the service sent unasked (`banners` scans)
+    pub banner: Option<String>,
+    /// How long the successful TCP connect took (open ports only)
+    pub latency: Option<Duration>,
+    /// Negotiated protocol and certificate of a TLS port (`tls` feature)
+    pub tls_info: Option<TlsInfo>,
 }
 
+impl ScanResult {
+    /// `443`, or `53/udp` for UDP
+    pub fn port_label(&self) -> String {
+        match self.protocol {
+            Protocol::Tcp => self.port.to_string(),
+            Protocol::Udp => format!("{}/udp", self.port),
+        }
+    }
+}
+
+/// Ports per worker thread when `ScanConfig::workers` is unset
 const WORKERS: usize = 64;
-const TIMEOUT_MS: u64 = 700;
+/// Connect timeout when `ScanConfig::timeout` is unset
+pub(crate) const TIMEOUT_MS: u64 = 700;
+/// How long a probe waits for the service to speak or answer
+const PROBE_READ_MS: u64 = 700;
+/// Most a banner grab reads
+pub const BANNER_BYTES: usize = 512;
+/// Connect timeout of the first sweep in `fast` mode
+const FAST_TIMEOUT_MS: u64 = 150;
+/// How long an accepted connection must survive to count as a real service
+const RESET_WINDOW_MS: u64 = 250;
+
+/// Service label for ports that accept, then immediately reset/close
+pub const NO_SERVICE: &str = "no service";
+
+/// Fewer probed ports than this never trigger [`ScanMetrics::filtered_hint`]
+const FILTERED_HINT_MIN: u64 = 10;
+/// Default cap on results kept in memory by [`Collector`]
+pub const MAX_RESULTS: usize = 1 << 20;
 
 // =======================
-// ENTRY
+// CONFIG
 // =======================
-pub fn scan(target: &Target, ports: &Ports) -> Vec<ScanResult> {
-    let host = target.host.clone();
-    let (tx, rx) = mpsc::channel::<ScanResult>();
-    let mut handles = Vec::new();
+/// Which engine drives the connects
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Thread-per-batch blocking connects (default)
+    #[default]
+    Threads,
+    /// Tokio runtime with many concurrent connects (`async` feature)
+    #[cfg(feature = "async")]
+    Async,
+}
 
-    for batch in ports.ports.chunks(WORKERS) {  
-        let host = host.clone();  
-        let tx = tx.clone();  
-        let list = batch.to_vec();  
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Threads => "threads",
+            #[cfg(feature = "async")]
+            Self::Async => "async",
+        }
+    }
+}
 
-        let h = thread::spawn(move || {  
-            for port in list {  
-                let result = scan_single(&host, port);  
-                let _ = tx.send(result);  
-            }  
-        });  
+/// Which resolved addresses of a host get scanned
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// Whatever the host resolves to (default)
+    #[default]
+    Any,
+    V4,
+    V6,
+}
 
-        handles.push(h);  
-    }  
+impl AddressFamily {
+    /// `any` / `v4` / `v6` (`4`, `6`, `ipv4`, `ipv6` too)
+    pub fn parse(name: &str) -> Option<AddressFamily> {
+        match name.to_ascii_lowercase().as_str() {
+            "any" => Some(AddressFamily::Any),
+            "v4" | "4" | "ipv4" => Some(AddressFamily::V4),
+            "v6" | "6" | "ipv6" => Some(AddressFamily::V6),
+            _ => None,
+        }
+    }
 
-    drop(tx);  
+    pub fn matches(&self, ip: IpAddr) -> bool {
+        match self {
+            AddressFamily::Any => true,
+            AddressFamily::V4 => ip.is_ipv4(),
+            AddressFamily::V6 => ip.is_ipv6(),
+        }
+    }
+}
 
-    let mut results = Vec::new();  
-    for r in rx {  
-        results.push(r);  
-    }  
+impl std::fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AddressFamily::Any => "any",
+            AddressFamily::V4 => "IPv4",
+            AddressFamily::V6 => "IPv6",
+        })
+    }
+}
 
-    for h in handles {  
-        let _ = h.join();  
-    }  
+/// Scan settings
+#[derive(Clone, Debug, Default)]
+pub struct ScanConfig {
+    pub backend: Backend,
+    /// Complete the handshake and hang up: no probes, no follow-up
+    /// connections, not a single application-layer byte sent
+    pub connect_only: bool,
+    /// Probe everything, but return only Open results
+    pub open_only: bool,
+    /// Record extra diagnostics such as the local address per connection
+    pub verbose: bool,
+    /// Time budget per host; ports not started by then are `Skipped`
+    pub host_timeout: Option<Duration>,
+    /// Tunnel every connect through this proxy (threaded backend, no probes)
+    pub proxy: Option<Proxy>,
+    /// Connect timeout; `None` means the built-in 700ms
+    pub timeout: Option<Duration>,
+    /// Extra connect attempts after a timeout; the last attempt decides,
+    /// so a port that answers a retry is Open
+    pub retries: u8,
+    /// Pause before each retry, so it doesn't land in the same burst of
+    /// congestion. Worst case per port (and address) is
+    /// `(retries + 1) * (timeout + retry_delay)`.
+    pub retry_delay: Duration,
+    /// Stop once this many results are held in memory; `None` means
+    /// [`MAX_RESULTS`]. Streaming sinks are not capped.
+    pub max_results: Option<usize>,
+    /// Ports each thread of the threaded backend takes, connects in
+    /// flight for the async one; `None` means 64 (async: 2048)
+    pub workers: Option<usize>,
+    /// Most ports started per second per host, whatever the worker
+    /// count; `None` means as fast as the workers go
+    pub max_rate: Option<u32>,
+    /// Nameserver for A/AAAA lookups; `None` means the system resolver
+    pub resolver: Option<SocketAddr>,
+    /// Sweep with a 150ms timeout first, then re-check only the ports
+    /// that timed out with the normal timeout and retries
+    pub fast: bool,
+    /// Bind every connect, probes included, to this interface's address
+    pub interface: Option<Interface>,
+    /// Workers hold off new connects while this is set; clones share it
+    pub pause: PauseFlag,
+    /// Reconnect to each open port and keep the first [`BANNER_BYTES`]
+    /// it sends (not with connect-only or a proxy)
+    pub banners: bool,
+    /// Scan only addresses of this family; a host with none is an error
+    pub family: AddressFamily,
+    /// Gets a [`ScanProgress`] each time a port's final result is in
+    /// (open_only-hidden ports included)
+    pub progress: Option<mpsc::Sender<ScanProgress>>,
+    pub cancel: CancelFlag,
+}
 
-    results.sort_by_key(|r| r.port);  
-    results
+/// How far one host's scan has got
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanProgress {
+    pub host: String,
+    /// Ports with a final result
+    pub completed: usize,
+    /// Ports in the scan
+    pub total: usize,
+}
+
+/// Shared pause switch for a running scan. Connects already in flight
+/// finish; no new port is started until [`PauseFlag::resume`].
+#[derive(Clone, Debug, Default)]
+pub struct PauseFlag(Arc<AtomicBool>);
+
+/// How often a paused worker checks whether to go on
+pub(crate) const PAUSE_POLL: Duration = Duration::from_millis(50);
+
+impl PauseFlag {
+    pub fn pause(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Block while paused, unless the scan is being stopped or cancelled
+    fn wait(&self, stop: &AtomicBool, cancel: &CancelFlag) {
+        while self.is_paused() && !stop.load(Ordering::Relaxed) && !cancel.is_cancelled() {
+            thread::sleep(PAUSE_POLL);
+        }
+    }
+}
+
+/// Shared stop switch for a running scan. Connects in flight finish, no
+/// new port is started, and the scan ends with [`ScanError::Cancelled`];
+/// results delivered so far stand.
+#[derive(Clone, Debug, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Start times spaced `1 / max_rate` apart, which the workers of one
+/// host's scan claim in turn
+pub(crate) struct Throttle {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl Throttle {
+    /// `None` when the config sets no rate
+    pub(crate) fn new(config: &ScanConfig) -> Option<Throttle> {
+        let rate = config.max_rate.filter(|&r| r > 0)?;
+        Some(Throttle {
+            interval: Duration::from_secs(1) / rate,
+            next: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Claim the next free start time (now, if the scan is behind)
+    pub(crate) fn slot(&self) -> Instant {
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+        let slot = (*next).max(Instant::now());
+        *next = slot + self.interval;
+        slot
+    }
+
+    /// Block until the next slot, unless the scan is being stopped or
+    /// cancelled
+    fn wait(&self, stop: &AtomicBool, cancel: &CancelFlag) {
+        let slot = self.slot();
+        while !stop.load(Ordering::Relaxed) && !cancel.is_cancelled() {
+            let now = Instant::now();
+            if now >= slot {
+                break;
+            }
+            thread::sleep(slot.saturating_duration_since(now).min(PAUSE_POLL));
+        }
+    }
+}
+
+impl ScanConfig {
+    /// Settings of a named [`Profile`] (`quick`, `default`, `thorough`)
+    pub fn profile(name: &str) -> Option<ScanConfig> {
+        Profile::parse(name).map(Profile::config)
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        self.timeout.unwrap_or(Duration::from_millis(TIMEOUT_MS))
+    }
+
+    pub fn result_cap(&self) -> usize {
+        self.max_results.unwrap_or(MAX_RESULTS)
+    }
+
+    /// Ports each worker thread takes; `workers: Some(0)` counts as 1
+    pub(crate) fn batch_size(&self) -> usize {
+        self.workers.unwrap_or(WORKERS).max(1)
+    }
+
+    /// Whether `ports` go through the async backend; proxied and UDP
+    /// scans always use threads
+    pub fn runs_async(&self, ports: &Ports) -> bool {
+        self.backend != Backend::Threads && self.proxy.is_none() && ports.protocol == Protocol::Tcp
+    }
+
+    /// Worker threads a scan of `ports` spawns, or for the async
+    /// backend the connects it keeps in flight
+    pub fn effective_workers(&self, ports: &Ports) -> usize {
+        #[cfg(feature = "async")]
+        if self.runs_async(ports) {
+            return self.workers.unwrap_or(super::async_scan::CONCURRENCY).max(1);
+        }
+        ports.ports.len().div_ceil(self.batch_size())
+    }
+}
+
+/// Named presets; individual flags override what they set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// top_basic, connect-only, 300ms
+    Quick,
+    /// top 1000, service probes and banners, 700ms
+    Default,
+    /// all ports, probes, banners and verbose diagnostics, 1500ms, one
+    /// retry on timeout
+    Thorough,
+}
+
+impl Profile {
+    pub fn parse(name: &str) -> Option<Profile> {
+        match name {
+            "quick" => Some(Profile::Quick),
+            "default" => Some(Profile::Default),
+            "thorough" => Some(Profile::Thorough),
+            _ => None,
+        }
+    }
+
+    pub fn config(self) -> ScanConfig {
+        match self {
+            Profile::Quick => ScanConfig {
+                connect_only: true,
+                timeout: Some(Duration::from_millis(300)),
+                ..Default::default()
+            },
+            Profile::Default => ScanConfig {
+                timeout: Some(Duration::from_millis(TIMEOUT_MS)),
+                banners: true,
+                ..Default::default()
+            },
+            Profile::Thorough => ScanConfig {
+                timeout: Some(Duration::from_millis(1500)),
+                retries: 1,
+                banners: true,
+                verbose: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn ports(self) -> Ports {
+        match self {
+            Profile::Quick => Ports::top_basic(),
+            Profile::Default => Ports::top_1000(),
+            Profile::Thorough => Ports::all(),
+        }
+    }
 }
 
 // =======================
-// CORE LOGIC
+// ERRORS
 // =======================
-fn scan_single(host: &str, port: u16) -> ScanResult {
-    let fallback_service = service_name(port);
-
-    let addrs = match (host, port).to_socket_addrs() {  
-        Ok(a) => a.collect::<Vec<_>>(),  
-        Err(_) => {  
-            return ScanResult {  
-                port,  
-                status: PortStatus::Filtered,  
-                service: fallback_service,  
-                os_hint: None,  
-                confidence: 0,  
-            };  
-        }  
-    };  
-
-    let mut saw_timeout = false;  
-
-    for addr in addrs {  
-        match tcp_connect(addr) {  
-            TcpResult::Open => {  
-                let service = protocol_probe(addr, host, port).unwrap_or(fallback_service);  
-                let os_hint = os_detect_signal(port, service);  
-                let confidence = confidence_score(service, &os_hint);  
-
-                return ScanResult {  
-                    port,  
-                    status: PortStatus::Open,  
-                    service,  
-                    os_hint,  
-                    confidence,  
-                };  
-            }  
-            TcpResult::Timeout => saw_timeout = true,  
-            TcpResult::Refused => {}  
-        }  
-    }  
-
-    ScanResult {  
-        port,  
-        status: if saw_timeout {  
-            PortStatus::Filtered  
-        } else {  
-            PortStatus::Closed  
-        },  
-        service: fallback_service,  
-        os_hint: None,  
-        confidence: 0,  
-    }
-}
-
-// =======================
-// TCP CONNECT
-// =======================
-enum TcpResult {
-    Open,
-    Refused,
-    Timeout,
+/// Something went wrong mid-scan; results already delivered still stand
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScanError {
+    /// Worker threads/tasks that panicked; their remaining ports are missing
+    WorkerPanicked { workers: usize },
+    /// The sink stopped accepting results, so the scan stopped early
+    ResultLimit,
+    /// The bound interface has no address of the host's family; the
+    /// host was not scanned
+    NoBindAddress {
+        interface: String,
+        host: String,
+        family: &'static str,
+    },
+    /// The host name did not resolve (once, before any port); the host
+    /// was not scanned
+    Unresolved { host: String, reason: String },
+    /// The host resolved, but to no address of the forced family; the
+    /// host was not scanned
+    NoAddressOfFamily { host: String, family: AddressFamily },
+    /// [`CancelFlag::cancel`] stopped the scan; later hosts are not scanned
+    Cancelled,
 }
 
-fn tcp_connect(addr: SocketAddr) -> TcpResult {
-    match TcpStream::connect_timeout(&addr, Duration::from_millis(TIMEOUT_MS)) {
-        Ok(_) => TcpResult::Open,
-        Err(e) => {
-            use std::io::ErrorKind::*;
-            match e.kind() {
-                TimedOut | WouldBlock => TcpResult::Timeout,
-                ConnectionRefused => TcpResult::Refused,
-                _ => TcpResult::Refused,
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanError::WorkerPanicked { workers } => {
+                write!(f, "{} scan worker(s) panicked; results are incomplete", workers)
             }
+            ScanError::ResultLimit => write!(f, "result limit reached; scan stopped early"),
+            ScanError::NoBindAddress { interface, host, family } => {
+                write!(f, "interface {} has no {} address to reach {}; host skipped", interface, family, host)
+            }
+            ScanError::Unresolved { host, reason } => write!(f, "cannot resolve {}: {}; host skipped", host, reason),
+            ScanError::NoAddressOfFamily { host, family } => {
+                write!(f, "{} has no {} address; host skipped", host, family)
+            }
+            ScanError::Cancelled => write!(f, "scan cancelled; results so far are kept"),
         }
     }
 }
 
+impl std::error::Error for ScanError {}
+
 // =======================
-// SERVICE DETECTION (PROBES)
+// METRICS
 // =======================
-fn protocol_probe(addr: SocketAddr, host: &str, port: u16) -> Option<&'static str> {
-    match port {
-        80 | 8080 | 8000 => http_probe(addr).then_some("HTTP"),
-        443 | 8443 => tls_probe(addr, host).then_some("HTTPS"),
-        22 => ssh_probe(addr).then_some("SSH"),
-        25 => smtp_probe(addr).then_some("SMTP"),
-        3306 => mysql_probe(addr).then_some("MYSQL"),
-        3389 => rdp_probe(addr).then_some("RDP"),
-        _ => None,
+/// Counters for benchmarking worker / timeout settings
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScanMetrics {
+    /// Ports with a result (skipped ones included)
+    pub ports: u64,
+    /// Connect attempts, retries included
+    pub connects: u64,
+    /// Connect attempts that timed out
+    pub timeouts: u64,
+    pub open: u64,
+    pub closed: u64,
+    pub filtered: u64,
+    /// Wall time spent scanning
+    pub elapsed: Duration,
+}
+
+impl ScanMetrics {
+    pub fn ports_per_sec(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.ports as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// Advice when over 80% of probed ports came back filtered, which
+    /// usually means a firewall or a timeout too short for the link
+    pub fn filtered_hint(&self, config: &ScanConfig) -> Option<String> {
+        let probed = self.open + self.closed + self.filtered;
+        if probed < FILTERED_HINT_MIN || self.filtered * 5 <= probed * 4 {
+            return None;
+        }
+
+        let longer = config.connect_timeout().as_millis() * 2;
+        let retry = match config.retries {
+            0 => " or --retries 1",
+            _ => "",
+        };
+        Some(format!(
+            "{}% of ports filtered (firewall, or timeout too short): try --timeout {}ms{}",
+            self.filtered * 100 / probed,
+            longer,
+            retry
+        ))
+    }
+
+    pub(crate) fn count(&mut self, r: &ScanResult) {
+        self.ports += 1;
+        match r.status {
+            PortStatus::Open => self.open += 1,
+            PortStatus::Closed => self.closed += 1,
+            PortStatus::Filtered => self.filtered += 1,
+            _ => {}
+        }
     }
 }
 
-fn http_probe(addr: SocketAddr) -> bool {
-    if let Ok(mut s) = TcpStream::connect_timeout(&addr, Duration::from_millis(TIMEOUT_MS)) {
-        let _ = s.write_all(b"HEAD / HTTP/1.1\r\nHost: x\r\n\r\n");
-        s.set_read_timeout(Some(Duration::from_millis(TIMEOUT_MS))).ok();
-        let mut buf = [0u8; 4];
-        return s.read(&mut buf).is_ok();
+impl std::fmt::Display for ScanMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ports ({} open, {} closed, {} filtered), {} connects, {} timeouts, {:.0} ports/s",
+            self.ports,
+            self.open,
+            self.closed,
+            self.filtered,
+            self.connects,
+            self.timeouts,
+            self.ports_per_sec()
+        )
     }
-    false
 }
 
-fn tls_probe(addr: SocketAddr, _host: &str) -> bool {
-    if let Ok(mut s) = TcpStream::connect_timeout(&addr, Duration::from_millis(TIMEOUT_MS)) {
-        s.set_read_timeout(Some(Duration::from_millis(TIMEOUT_MS))).ok();
-        let _ = s.write_all(&tls_client_hello());
-        let mut buf = [0u8; 1];
-        return s.read(&mut buf).is_ok();
+/// Connect counters shared by the workers of one scan
+#[derive(Default)]
+pub(crate) struct Meter {
+    connects: AtomicU64,
+    timeouts: AtomicU64,
+}
+
+impl Meter {
+    pub(crate) fn record(&self, outcome: &TcpResult) {
+        self.connects.fetch_add(1, Ordering::Relaxed);
+        if *outcome == TcpResult::Timeout {
+            self.timeouts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn add_to(&self, metrics: &mut ScanMetrics) {
+        metrics.connects += self.connects.load(Ordering::Relaxed);
+        metrics.timeouts += self.timeouts.load(Ordering::Relaxed);
     }
-    false
 }
 
-fn ssh_probe(addr: SocketAddr) -> bool {
-    if let Ok(mut s) = TcpStream::connect_timeout(&addr, Duration::from_millis(TIMEOUT_MS)) {
-        s.set_read_timeout(Some(Duration::from_millis(TIMEOUT_MS))).ok();
-        let mut buf = [0u8; 4];
-        if s.read(&mut buf).is_ok() {
-            return &buf == b"SSH-";
+/// Connector wrapper feeding a [`Meter`]
+struct Metered<'a, C> {
+    inner: &'a C,
+    meter: &'a Meter,
+}
+
+impl<C: Connector> Connector for Metered<'_, C> {
+    fn connect(&self, addr: SocketAddr, timeout: Duration) -> TcpResult {
+        let outcome = self.inner.connect(addr, timeout);
+        self.meter.record(&outcome);
+        outcome
+    }
+}
+
+// =======================
+// CONNECTOR
+// =======================
+/// Outcome of a single TCP connect attempt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TcpResult {
+    /// Connected; carries our local address when the connector knows it
+    Open(Option<SocketAddr>),
+    Refused,
+    Timeout,
+    /// The connect failed without an answer from the port (no route, a
+    /// local firewall rule, any other OS error); carries the error kind
+    Unreachable(std::io::ErrorKind),
+    /// The proxy answered with this non-success status instead of a tunnel
+    ProxyDenied(u16),
+}
+
+/// Opens (or pretends to open) TCP connections for the scanner.
+///
+/// Production code uses [`RealConnector`]; tests can inject scripted
+/// outcomes to exercise the Open / Closed / Filtered decision tree
+/// without touching the network.
+pub trait Connector: Sync {
+    fn connect(&self, addr: SocketAddr, timeout: Duration) -> TcpResult;
+}
+
+/// Plain `TcpStream::connect_timeout` connector
+pub struct RealConnector;
+
+impl Connector for RealConnector {
+    fn connect(&self, addr: SocketAddr, timeout: Duration) -> TcpResult {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(s) => TcpResult::Open(s.local_addr().ok()),
+            Err(e) => classify_error(&e),
         }
     }
-    false
 }
 
-fn smtp_probe(addr: SocketAddr) -> bool {
-    if let Ok(mut s) = TcpStream::connect_timeout(&addr, Duration::from_millis(TIMEOUT_MS)) {
-        s.set_read_timeout(Some(Duration::from_millis(TIMEOUT_MS))).ok();
-        let mut buf = [0u8; 3];
-        return s.read(&mut buf).is_ok();
+pub(crate) fn classify_error(e: &std::io::Error) -> TcpResult {
+    use std::io::ErrorKind::*;
+    match e.kind() {
+        TimedOut | WouldBlock => TcpResult::Timeout,
+        // Only a refusal is the port itself answering "closed"
+        ConnectionRefused => TcpResult::Refused,
+        kind => TcpResult::Unreachable(kind),
     }
-    false
 }
 
-fn mysql_probe(addr: SocketAddr) -> bool {
-    if let Ok(mut s) = TcpStream::connect_timeout(&addr, Duration::from_millis(TIMEOUT_MS)) {
-        s.set_read_timeout(Some(Duration::from_millis(TIMEOUT_MS))).ok();
-        let mut buf = [0u8; 1];
-        return s.read(&mut buf).is_ok();
+/// Status of a port whose every address failed with `kind`: a missing
+/// route is Unreachable, anything else (a local firewall denial,
+/// `EPERM`/`EACCES`, or another OS error) Filtered
+pub fn unreachable_status(kind: std::io::ErrorKind) -> PortStatus {
+    match kind {
+        std::io::ErrorKind::NetworkUnreachable | std::io::ErrorKind::HostUnreachable => PortStatus::Unreachable,
+        _ => PortStatus::Filtered,
     }
-    false
 }
 
-fn rdp_probe(addr: SocketAddr) -> bool {
-    if let Ok(mut s) = TcpStream::connect_timeout(&addr, Duration::from_millis(TIMEOUT_MS)) {
-        s.set_read_timeout(Some(Duration::from_millis(TIMEOUT_MS))).ok();
-        let mut buf = [0u8; 1];
-        return s.read(&mut buf).is_ok();
+// =======================
+// RESULT SINK
+// =======================
+/// Receives results as ports finish, in completion order.
+///
+/// `Vec<ScanResult>` is the default collector; implement this to stream
+/// results elsewhere (a UI, NDJSON, a database) without waiting for the
+/// whole scan.
+pub trait ResultSink {
+    fn on_result(&mut self, result: &ScanResult);
+
+    /// `false` stops the scan (e.g. a memory cap was hit)
+    fn wants_more(&self) -> bool {
+        true
+    }
+
+    /// Per-host findings once a host is done (verbose scans only)
+    fn on_host(&mut self, _info: &HostInfo) {}
+}
+
+impl ResultSink for Vec<ScanResult> {
+    fn on_result(&mut self, result: &ScanResult) {
+        self.push(result.clone());
+    }
+}
+
+/// In-memory collector that stops the scan after `cap` results
+pub struct Collector {
+    pub results: Vec<ScanResult>,
+    cap: usize,
+}
+
+impl Collector {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            results: Vec::new(),
+            cap,
+        }
+    }
+}
+
+impl ResultSink for Collector {
+    fn on_result(&mut self, result: &ScanResult) {
+        if self.results.len() < self.cap {
+            self.results.push(result.clone());
+        }
+    }
+
+    fn wants_more(&self) -> bool {
+        self.results.len() < self.cap
     }
-    false
 }
 
 // =======================
-// OS SIGNAL
+// ENTRY
 // =======================
-fn os_detect_signal(port: u16, service: &str) -> Option<&'static str> {
-    match (port, service) {
-        (445, "SMB") | (3389, "RDP") => Some("Windows"),
-        (22, "SSH") => Some("Unix-like"),
-        (80, "HTTP") | (443, "HTTPS") => Some("Unix-like"),
-        _ => None,
+pub fn scan(target: &Target, ports: &Ports) -> Vec<ScanResult> {
+    scan_with_config(target, ports, &ScanConfig::default())
+}
+
+/// Same as [`scan`], dispatching to the backend chosen in `config`.
+/// A [`ScanError`] is dropped here; use [`scan_into`] to see it.
+pub fn scan_with_config(target: &Target, ports: &Ports, config: &ScanConfig) -> Vec<ScanResult> {
+    let mut collector = Collector::new(config.result_cap());
+    let _ = scan_into(target, ports, config, &mut collector);
+    sort_results(&mut collector.results);
+    if !config.open_only {
+        host_verdicts(&mut collector.results);
     }
+    collector.results
+}
+
+/// Stream results into `sink` as they arrive (unsorted). On `Err`, the
+/// sink still got every result that was produced.
+pub fn scan_into<S: ResultSink>(
+    target: &Target,
+    ports: &Ports,
+    config: &ScanConfig,
+    sink: &mut S,
+) -> Result<(), ScanError> {
+    scan_into_metered(target, ports, config, sink, &mut ScanMetrics::default())
+}
+
+/// [`scan_into`], adding this scan's counters to `metrics` (so one
+/// `ScanMetrics` can total several hosts)
+pub fn scan_into_metered<S: ResultSink>(
+    target: &Target,
+    ports: &Ports,
+    config: &ScanConfig,
+    sink: &mut S,
+    metrics: &mut ScanMetrics,
+) -> Result<(), ScanError> {
+    let started = Instant::now();
+
+    // Proxied and UDP scans always go through the threaded backend
+    let outcome = scan_passes(ports, config, sink, metrics, |ports, config, sink, metrics| {
+        match (&config.proxy, config.backend) {
+            (Some(proxy), _) => scan_threads(target, ports, config, proxy, sink, metrics),
+            #[cfg(feature = "async")]
+            (None, Backend::Async) if config.runs_async(ports) => {
+                super::async_scan::scan(target, ports, config, sink, metrics)
+            }
+            (None, _) => match &config.interface {
+                Some(iface) => scan_threads(target, ports, config, iface, sink, metrics),
+                None => scan_threads(target, ports, config, &RealConnector, sink, metrics),
+            },
+        }
+    });
+
+    metrics.elapsed += started.elapsed();
+    outcome
+}
+
+/// Threaded scan through the given [`Connector`], sorted by port
+pub fn scan_with<C: Connector>(
+    target: &Target,
+    ports: &Ports,
+    config: &ScanConfig,
+    connector: &C,
+) -> Vec<ScanResult> {
+    let mut results = Vec::new();
+    let _ = scan_with_sink(target, ports, config, connector, &mut results);
+    sort_results(&mut results);
+    results
+}
+
+/// Threaded scan through the given [`Connector`], streaming into `sink`
+pub fn scan_with_sink<C: Connector, S: ResultSink>(
+    target: &Target,
+    ports: &Ports,
+    config: &ScanConfig,
+    connector: &C,
+    sink: &mut S,
+) -> Result<(), ScanError> {
+    scan_passes(ports, config, sink, &mut ScanMetrics::default(), |ports, config, sink, metrics| {
+        scan_threads(target, ports, config, connector, sink, metrics)
+    })
 }
 
 // =======================
-// CONFIDENCE SCORE
+// FAST MODE
 // =======================
-fn confidence_score(service: &str, os: &Option<&str>) -> u8 {
-    match (service, os) {
-        ("SSH", Some(_)) => 95,
-        ("HTTP", Some(_)) => 90,
-        ("HTTPS", Some(_)) => 90,
-        ("RDP", Some("Windows")) => 95,
-        ("SMTP", _) => 85,
-        ("MYSQL", _) => 85,
-        ("unknown", _) => 30,
-        _ => 60,
+/// Sits between a scan pass and the caller's sink. On a `fast` first
+/// sweep it holds timeouts back for verification instead of reporting them.
+struct Sweep<'a, S> {
+    sink: &'a mut S,
+    config: &'a ScanConfig,
+    /// Ports that timed out in the first sweep; `None` on a final pass
+    timed_out: Option<Vec<u16>>,
+    /// Ports with a final result, over all passes
+    completed: usize,
+    total: usize,
+}
+
+impl<S: ResultSink> ResultSink for Sweep<'_, S> {
+    fn on_result(&mut self, result: &ScanResult) {
+        if let Some(ports) = &mut self.timed_out
+            && matches!(result.status, PortStatus::Filtered | PortStatus::OpenFiltered)
+        {
+            ports.push(result.port);
+            return;
+        }
+
+        self.completed += 1;
+        if let Some(tx) = &self.config.progress {
+            let _ = tx.send(ScanProgress {
+                host: result.host.clone(),
+                completed: self.completed,
+                total: self.total,
+            });
+        }
+        if keep(self.config, result) {
+            self.sink.on_result(result);
+        }
+    }
+
+    fn wants_more(&self) -> bool {
+        self.sink.wants_more()
+    }
+
+    fn on_host(&mut self, info: &HostInfo) {
+        self.sink.on_host(info);
+    }
+}
+
+/// Run `pass` once, or for `config.fast` twice: every port with
+/// [`FAST_TIMEOUT_MS`] and no retries, then the ports that timed out
+/// with the normal timeout and retries. Only a port's final outcome
+/// reaches `sink`.
+fn scan_passes<'a, S: ResultSink>(
+    ports: &Ports,
+    config: &'a ScanConfig,
+    sink: &'a mut S,
+    metrics: &mut ScanMetrics,
+    mut pass: impl FnMut(&Ports, &ScanConfig, &mut Sweep<'a, S>, &mut ScanMetrics) -> Result<(), ScanError>,
+) -> Result<(), ScanError> {
+    // Passes see every result; `Sweep` applies open_only on the way out
+    let all = ScanConfig {
+        open_only: false,
+        fast: false,
+        ..config.clone()
+    };
+    let mut sweep = Sweep {
+        sink,
+        config,
+        timed_out: config.fast.then(Vec::new),
+        completed: 0,
+        total: ports.ports.len(),
+    };
+
+    if !config.fast {
+        return pass(ports, &all, &mut sweep, metrics);
+    }
+
+    let first = ScanConfig {
+        timeout: Some(Duration::from_millis(FAST_TIMEOUT_MS)),
+        retries: 0,
+        ..all.clone()
+    };
+    let started = Instant::now();
+    let mut first_metrics = ScanMetrics::default();
+    let outcome = pass(ports, &first, &mut sweep, &mut first_metrics);
+    let timed_out = sweep.timed_out.take().unwrap_or_default();
+
+    let mut verify_metrics = ScanMetrics::default();
+    let outcome = outcome.and_then(|()| {
+        let all = ScanConfig {
+            host_timeout: config.host_timeout.map(|t| t.saturating_sub(started.elapsed())),
+            ..all
+        };
+        match timed_out.is_empty() {
+            true => Ok(()),
+            false => {
+                let ports = Ports::multiple(timed_out).with_protocol(ports.protocol);
+                pass(&ports, &all, &mut sweep, &mut verify_metrics)
+            }
+        }
+    });
+
+    // Each port counts once, with its verified outcome
+    metrics.ports += first_metrics.ports;
+    metrics.connects += first_metrics.connects + verify_metrics.connects;
+    metrics.timeouts += first_metrics.timeouts + verify_metrics.timeouts;
+    metrics.open += first_metrics.open + verify_metrics.open;
+    metrics.closed += first_metrics.closed + verify_metrics.closed;
+    metrics.filtered += match outcome {
+        Ok(()) => verify_metrics.filtered,
+        Err(_) => first_metrics.filtered,
+    };
+    outcome
+}
+
+pub(crate) fn scan_threads<C: Connector, S: ResultSink>(
+    target: &Target,
+    ports: &Ports,
+    config: &ScanConfig,
+    connector: &C,
+    sink: &mut S,
+    metrics: &mut ScanMetrics,
+) -> Result<(), ScanError> {
+    let meter = Meter::default();
+    let connector = &Metered {
+        inner: connector,
+        meter: &meter,
+    };
+    let host = target.host.as_str();
+    let ips = of_family(config, host, resolve_host(config, host)?)?;
+    let ips = bindable(config, host, ips)?;
+    let setup = &HostSetup::new(host, ips, config);
+    let deadline = config.host_timeout.map(|t| Instant::now() + t);
+    let throttle = &Throttle::new(config);
+    let (tx, rx) = mpsc::channel::<ScanResult>();
+    let stop = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        let mut workers = Vec::new();
+
+        for batch in ports.ports.chunks(config.batch_size()) {
+            let (tx, stop) = (tx.clone(), &stop);
+
+            workers.push(s.spawn(move || {
+                for &port in batch {
+                    config.pause.wait(stop, &config.cancel);
+                    if let Some(throttle) = throttle {
+                        throttle.wait(stop, &config.cancel);
+                    }
+                    if stop.load(Ordering::Relaxed) || config.cancel.is_cancelled() {
+                        break;
+                    }
+                    let result = match ports.protocol {
+                        _ if deadline.is_some_and(|d| Instant::now() >= d) => skipped(host, port),
+                        Protocol::Tcp => scan_single(connector, config, host, setup, port),
+                        Protocol::Udp => udp_scan_single(config, host, setup, port),
+                    };
+                    let _ = tx.send(result);
+                }
+            }));
+        }
+
+        drop(tx);
+
+        // Workers keep going while the sink consumes on this thread
+        let mut answered = Answered::default();
+        for r in rx {
+            metrics.count(&r);
+            answered.see(setup, &r);
+            if keep(config, &r) {
+                sink.on_result(&r);
+            }
+            if !sink.wants_more() {
+                stop.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+
+        // Joined by hand so a panic is counted instead of re-raised
+        let failed = workers.into_iter().map(|w| w.join()).filter(Result::is_err).count();
+        meter.add_to(metrics);
+        if stop.load(Ordering::Relaxed) {
+            return Err(ScanError::ResultLimit);
+        }
+        if config.cancel.is_cancelled() {
+            return Err(ScanError::Cancelled);
+        }
+        // The TTL is measured with TCP connects
+        let answered = answered.addr().filter(|_| ports.protocol == Protocol::Tcp);
+        if let Some(info) = answered.and_then(|addr| host_info(config, setup, host, addr)) {
+            sink.on_host(&info);
+        }
+        match failed {
+            0 => Ok(()),
+            n => Err(ScanError::WorkerPanicked { workers: n }),
+        }
+    })
+}
+
+/// Sort by port, keeping each host's results together (hosts stay in
+/// the order they first appear). Stable.
+pub fn sort_results(results: &mut [ScanResult]) {
+    let mut hosts: Vec<&str> = Vec::new();
+    for r in results.iter() {
+        if !hosts.contains(&r.host.as_str()) {
+            hosts.push(&r.host);
+        }
     }
+    if hosts.len() <= 1 {
+        results.sort_by_key(|r| r.port);
+        return;
+    }
+
+    let order: Vec<String> = hosts.into_iter().map(String::from).collect();
+    results.sort_by_key(|r| (order.iter().position(|h| *h == r.host), r.port));
 }
 
 // =======================
-// FALLBACK SERVICE DB
+// HOST VERDICT
 // =======================
-fn service_name(port: u16) -> &'static str {
-    match port {
-        1..=19 => "system",
-        20 | 21 => "FTP",
-        22 => "SSH",
-        23 => "TELNET",
-        25 => "SMTP",
-        53 => "DNS",
-        80 => "HTTP",
-        110 => "POP3",
-        143 => "IMAP",
-        443 => "HTTPS",
-        445 => "SMB",
-        3306 => "MYSQL",
-        3389 => "RDP",
-        5432 => "POSTGRES",
-        6379 => "REDIS",
-        8080 => "HTTP-ALT",
-        _ => "unknown",
+/// What a host's mix of outcomes says about it, beyond single ports
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostVerdict {
+    /// Every probed port answered (open or refused)
+    Up,
+    /// Refuses nearly everything, so the few timeouts are per-port
+    /// firewall rules rather than packet loss
+    RuleFiltered,
+    /// Drops nearly everything; the few answers are holes in a
+    /// default-drop firewall
+    DefaultDrop,
+    /// Not a single answer: down, or filtered at the network edge
+    Down,
+    /// No clear pattern
+    Mixed,
+}
+
+impl std::fmt::Display for HostVerdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            HostVerdict::Up => "up, every port answered",
+            HostVerdict::RuleFiltered => "up, a few ports filtered by firewall rules",
+            HostVerdict::DefaultDrop => "behind a default-drop firewall",
+            HostVerdict::Down => "down or filtered at the edge",
+            HostVerdict::Mixed => "up, mixed closed and filtered ports",
+        })
+    }
+}
+
+/// Ports needed before a lopsided mix counts as a pattern
+const VERDICT_MIN_PORTS: usize = 10;
+
+/// Per-host verdicts (hosts in first-seen order). Filtered results get
+/// the verdict's explanation as their detail, so each port reads in the
+/// light of the whole host. Needs every result, including closed ones.
+pub fn host_verdicts(results: &mut [ScanResult]) -> Vec<(String, HostVerdict)> {
+    let mut hosts: Vec<(String, [usize; 3])> = Vec::new();
+    for r in results.iter() {
+        let slot = match r.status {
+            PortStatus::Open | PortStatus::Closed => 0,
+            PortStatus::Filtered | PortStatus::OpenFiltered | PortStatus::Unreachable => 1,
+            _ => 2,
+        };
+        match hosts.iter_mut().find(|(h, _)| *h == r.host) {
+            Some((_, counts)) => counts[slot] += 1,
+            None => {
+                let mut counts = [0; 3];
+                counts[slot] += 1;
+                hosts.push((r.host.clone(), counts));
+            }
+        }
     }
+
+    let verdicts: Vec<(String, HostVerdict)> = hosts
+        .into_iter()
+        .map(|(host, [answered, filtered, _])| {
+            let probed = answered + filtered;
+            let verdict = if filtered == 0 {
+                HostVerdict::Up
+            } else if answered == 0 {
+                HostVerdict::Down
+            } else if probed < VERDICT_MIN_PORTS {
+                HostVerdict::Mixed
+            } else if filtered * 10 <= probed {
+                HostVerdict::RuleFiltered
+            } else if answered * 10 <= probed {
+                HostVerdict::DefaultDrop
+            } else {
+                HostVerdict::Mixed
+            };
+            (host, verdict)
+        })
+        .collect();
+
+    for r in results.iter_mut().filter(|r| r.status == PortStatus::Filtered) {
+        let note = match verdicts.iter().find(|(h, _)| *h == r.host).map(|v| v.1) {
+            Some(HostVerdict::RuleFiltered) => "filtered by a firewall rule",
+            Some(HostVerdict::Down) => "host down or filtered at the edge",
+            _ => continue,
+        };
+        r.detail.get_or_insert_with(|| note.to_string());
+    }
+    verdicts
 }
 
 // =======================
-// TLS CLIENT HELLO (FULL, TLS 1.2)
+// HOST DISTANCE (TTL)
 // =======================
-fn tls_client_hello() -> Vec<u8> {
-    vec![
-        0x16, 0x03, 0x01, 0x00, 0x4f,
-        0x01, 0x00, 0x00, 0x4b,
-        0x03, 0x03,
-        0x53, 0x43, 0x4f, 0x52, 0x45, 0x00, 0x01, 0x02,
-        0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a,
-        0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12,
-        0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a,
-        0x00,
-        0x00, 0x06, 0x00, 0x2f, 0x00, 0x35, 0x00, 0x0a,
-        0x01, 0x00,
-        0x00, 0x0d, 0x00, 0x0a, 0x00, 0x04, 0x00, 0x02, 0x00, 0x17,
-        0x00, 0x0b, 0x00, 0x02, 0x01, 0x00,
-    ]
+/// What a finished host revealed beyond its ports
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HostInfo {
+    pub host: String,
+    /// IP TTL (hop limit) of the host's packets as they reached us
+    pub ttl: u8,
 }
 
+impl HostInfo {
+    /// Usual initial TTLs: 64 (Linux, macOS, BSD), 128 (Windows), 255
+    /// (network gear); the smallest one at or above what arrived
+    pub fn initial_ttl(&self) -> u8 {
+        [32, 64, 128, 255].into_iter().find(|&t| t >= self.ttl).unwrap_or(255)
+    }
+
+    /// Routers between us and the host, assuming [`HostInfo::initial_ttl`]
+    pub fn hops(&self) -> u8 {
+        self.initial_ttl() - self.ttl
+    }
+}
+
+impl std::fmt::Display for HostInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let os = match self.initial_ttl() {
+            64 => "Unix-like",
+            128 => "Windows",
+            255 => "network device",
+            _ => "unknown",
+        };
+        write!(f, "ttl {}, ~{} hops (initial {}, {})", self.ttl, self.hops(), self.initial_ttl(), os)
+    }
+}
+
+/// Where to measure a host's TTL: a refused port if there is one (the
+/// repeated connects then open nothing), else an open one
+#[derive(Default)]
+pub(crate) struct Answered {
+    refused: Option<SocketAddr>,
+    open: Option<SocketAddr>,
+}
+
+impl Answered {
+    pub(crate) fn see(&mut self, setup: &HostSetup, r: &ScanResult) {
+        match r.status {
+            // Closed means every address refused, so the first one did
+            PortStatus::Closed => {
+                if let Some(&ip) = setup.ips.first() {
+                    self.refused.get_or_insert(SocketAddr::new(ip, r.port));
+                }
+            }
+            PortStatus::Open => {
+                if let Some(addr) = r.remote_addr {
+                    self.open.get_or_insert(addr);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn addr(&self) -> Option<SocketAddr> {
+        self.refused.or(self.open)
+    }
+}
+
+/// TTL of an up host, on verbose scans that may send follow-up connects
+pub(crate) fn host_info(config: &ScanConfig, setup: &HostSetup, host: &str, addr: SocketAddr) -> Option<HostInfo> {
+    if !config.verbose || config.connect_only || config.proxy.is_some() {
+        return None;
+    }
+    measure_ttl(setup, addr, config.connect_timeout()).map(|ttl| HostInfo {
+        host: host.to_string(),
+        ttl,
+    })
+}
+
+/// Plain connects can't read the TTL of the SYN-ACK, but IP_MINTTL
+/// makes the kernel drop packets below a threshold: a connect that
+/// still gets an answer (accept or RST) means the TTL is at least
+/// that. Binary search over 1..=255 takes 8 connects; dropped ones wait
+/// a few round trips.
+#[cfg(target_os = "linux")]
+fn measure_ttl(setup: &HostSetup, addr: SocketAddr, timeout: Duration) -> Option<u8> {
+    let started = Instant::now();
+    if !answers_at_min_ttl(setup, addr, 1, timeout)? {
+        return None;
+    }
+    let wait = (started.elapsed() * 4).clamp(Duration::from_millis(50), timeout);
+
+    let (mut lo, mut hi) = (1u8, 255u8);
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        if answers_at_min_ttl(setup, addr, mid, wait)? {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    Some(lo)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn measure_ttl(_setup: &HostSetup, _addr: SocketAddr, _timeout: Duration) -> Option<u8> {
+    None
+}
+
+/// Whether `addr` answers when packets below `min` TTL are dropped;
+/// `None` when the socket option is not supported
+#[cfg(target_os = "linux")]
+fn answers_at_min_ttl(setup: &HostSetup, addr: SocketAddr, min: u8, wait: Duration) -> Option<bool> {
+    use socket2::{Domain, Protocol, Socket, Type};
+    use std::os::fd::AsRawFd;
+
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP)).ok()?;
+    let (level, name) = match addr {
+        SocketAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_MINTTL),
+        SocketAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_MINHOPCOUNT),
+    };
+    let value = libc::c_int::from(min);
+    // SAFETY: a live socket, and a c_int option value of the size we pass
+    let set = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            (&value as *const libc::c_int).cast(),
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if set != 0 {
+        return None;
+    }
+    if let Some(local) = setup.interface.as_ref().and_then(|i| i.bind_addr(addr.ip())) {
+        socket.bind(&SocketAddr::new(local, 0).into()).ok()?;
+    }
+
+    Some(match socket.connect_timeout(&addr.into(), wait) {
+        Ok(_) => true,
+        Err(e) => e.kind() == std::io::ErrorKind::ConnectionRefused,
+    })
+}
+
+// =======================
+// CHANGES
+// =======================
+/// (host, port)
+pub type HostPort = (String, u16);
+
+/// Open ports that differ between two runs: `(opened, closed)` as
+/// (host, port), sorted. Closed means no longer open, whatever the new
+/// state.
+pub fn open_changes(before: &[ScanResult], after: &[ScanResult]) -> (Vec<HostPort>, Vec<HostPort>) {
+    let open = |results: &[ScanResult]| -> BTreeSet<HostPort> {
+        results
+            .iter()
+            .filter(|r| r.status == PortStatus::Open)
+            .map(|r| (r.host.clone(), r.port))
+            .collect()
+    };
+    let (before, after) = (open(before), open(after));
+    (
+        after.difference(&before).cloned().collect(),
+        before.difference(&after).cloned().collect(),
+    )
+}
+
+/// `open_only` filter, shared by both backends
+pub(crate) fn keep(config: &ScanConfig, result: &ScanResult) -> bool {
+    !config.open_only || result.status == PortStatus::Open
+}
+
+// =======================
+// CORE LOGIC
+// =======================
+/// Addresses of `host`, through `config.resolver` when one is set
+pub(crate) fn resolve(config: &ScanConfig, host: &str) -> std::io::Result<Vec<IpAddr>> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(vec![ip]);
+    }
+    match config.resolver {
+        Some(server) => dns::lookup(server, host, config.connect_timeout().max(Duration::from_secs(2))),
+        None => Ok((host, 0).to_socket_addrs()?.map(|a| a.ip()).collect()),
+    }
+}
+
+/// Per-host work done once, before any port: the addresses, and probe
+/// payloads that depend on the host name
+pub(crate) struct HostSetup {
+    pub ips: Vec<IpAddr>,
+    /// ClientHello for `tls_probe`, with SNI when the host is a name
+    hello: Vec<u8>,
+    /// Server name the TLS handshake sends, when the host is a name
+    #[cfg(feature = "tls")]
+    sni: Option<String>,
+    /// Probes connect from here too
+    interface: Option<Interface>,
+    /// Connect timeout of the scan, used by the probes' connects too
+    timeout: Duration,
+}
+
+impl HostSetup {
+    pub(crate) fn new(host: &str, ips: Vec<IpAddr>, config: &ScanConfig) -> Self {
+        let sni = host.parse::<IpAddr>().is_err().then_some(host);
+        Self {
+            ips,
+            hello: tls_client_hello(sni),
+            #[cfg(feature = "tls")]
+            sni: sni.map(str::to_string),
+            interface: config.interface.clone(),
+            timeout: config.connect_timeout(),
+        }
+    }
+
+    /// Probe connection, through the bound interface if there is one
+    fn dial(&self, addr: SocketAddr) -> std::io::Result<TcpStream> {
+        match &self.interface {
+            Some(iface) => iface.connect_stream(addr, self.timeout),
+            None => TcpStream::connect_timeout(&addr, self.timeout),
+        }
+    }
+
+    /// [`grab_banner`] through [`HostSetup::dial`]
+    fn grab_banner(&self, addr: SocketAddr) -> Option<String> {
+        let mut s = self.dial(addr).ok()?;
+        read_banner(&mut s, Duration::from_millis(PROBE_READ_MS), BANNER_BYTES)
+    }
+}
+
+/// [`resolve`] for a scan: every worker connects to these addresses, so
+/// a host costs one lookup however many ports it has. No address at
+/// all is an error.
+pub(crate) fn resolve_host(config: &ScanConfig, host: &str) -> Result<Vec<IpAddr>, ScanError> {
+    let unresolved = |reason: String| ScanError::Unresolved {
+        host: host.to_string(),
+        reason,
+    };
+    match resolve(config, host) {
+        Ok(ips) if ips.is_empty() => Err(unresolved("no addresses".into())),
+        Ok(ips) => Ok(ips),
+        Err(e) => Err(unresolved(e.to_string())),
+    }
+}
+
+/// Addresses of `config.family`; an error when `host` resolved only to
+/// the other family
+pub(crate) fn of_family(config: &ScanConfig, host: &str, mut ips: Vec<IpAddr>) -> Result<Vec<IpAddr>, ScanError> {
+    ips.retain(|&ip| config.family.matches(ip));
+    if ips.is_empty() {
+        return Err(ScanError::NoAddressOfFamily {
+            host: host.to_string(),
+            family: config.family,
+        });
+    }
+    Ok(ips)
+}
+
+/// Addresses the bound interface can connect to; an error when it has
+/// none of the family `host` resolved to
+pub(crate) fn bindable(config: &ScanConfig, host: &str, mut ips: Vec<IpAddr>) -> Result<Vec<IpAddr>, ScanError> {
+    let (Some(iface), Some(&first)) = (&config.interface, ips.first()) else {
+        return Ok(ips);
+    };
+    ips.retain(|&ip| iface.bind_addr(ip).is_some());
+    if ips.is_empty() {
+        return Err(ScanError::NoBindAddress {
+            interface: iface.name.clone(),
+            host: host.to_string(),
+            family: if first.is_ipv4() { "IPv4" } else { "IPv6" },
+        });
+    }
+    Ok(ips)
+}
+
+fn scan_single<C: Connector>(
+    connector: &C,
+    config: &ScanConfig,
+    host: &str,
+    setup: &HostSetup,
+    port: u16,
+) -> ScanResult {
+    if setup.ips.is_empty() {
+        return unanswered(host, port, true);
+    }
+
+    let mut saw_timeout = false;
+    let mut saw_refusal = false;
+    let mut failure = None;
+
+    'addrs: for &ip in &setup.ips {
+        let addr = SocketAddr::new(ip, port);
+        // Only a timeout is worth another attempt
+        for attempt in 0..=config.retries {
+            if attempt > 0 && !config.retry_delay.is_zero() {
+                thread::sleep(config.retry_delay);
+            }
+            let started = Instant::now();
+            match connector.connect(addr, config.connect_timeout()) {
+                TcpResult::Open(local) => {
+                    return open_result(config, setup, addr, local, started.elapsed(), host, port);
+                }
+                TcpResult::Timeout => saw_timeout = true,
+                TcpResult::Refused => {
+                    saw_refusal = true;
+                    continue 'addrs;
+                }
+                TcpResult::Unreachable(kind) => {
+                    failure = Some(kind);
+                    continue 'addrs;
+                }
+                TcpResult::ProxyDenied(_) => return proxy_denied(host, port),
+            }
+        }
+    }
+
+    match failure {
+        Some(kind) if !saw_timeout && !saw_refusal => unreached(host, port, kind),
+        _ => unanswered(host, port, saw_timeout),
+    }
+}
+
+/// Probe an open port and build its result
+pub(crate) fn open_result(
+    config: &ScanConfig,
+    setup: &HostSetup,
+    addr: SocketAddr,
+    local: Option<SocketAddr>,
+    latency: Duration,
+    host: &str,
+    port: u16,
+) -> ScanResult {
+    let local_addr = local.filter(|_| config.verbose);
+
+    // Probes would connect directly, around the proxy
+    if config.connect_only || config.proxy.is_some() {
+        let service = service_for(port);
+        return ScanResult {
+            host: host.to_string(),
+            port,
+            protocol: Protocol::Tcp,
+            status: PortStatus::Open,
+            service: service.to_string(),
+            os_hint: None,
+            confidence: confidence_score(service, &None),
+            detail: None,
+            remote_addr: Some(addr),
+            local_addr,
+            banner: None,
+            latency: Some(latency),
+            tls_info: None,
+        };
+    }
+
+    let probed = match protocol_probe(addr, setup, port) {
+        Some(probed) => probed,
+        // No probe confirmed anything: make sure the port isn't a tarpit
+        // that completes the handshake and drops us straight away
+        None if closes_after_accept(addr, setup) => Probed::named(NO_SERVICE, None),
+        None => Probed::named(service_for(port), None),
+    };
+    let os_hint = os_detect_signal(port, probed.kind);
+    let confidence = confidence_score(probed.kind, &os_hint);
+    let Probed { kind, service, detail, tls } = probed;
+    let banner = match config.banners {
+        true => setup.grab_banner(addr),
+        false => None,
+    };
+
+    ScanResult {
+        host: host.to_string(),
+        port,
+        protocol: Protocol::Tcp,
+        status: PortStatus::Open,
+        service: service.unwrap_or_else(|| kind.to_string()),
+        os_hint,
+        confidence,
+        detail,
+        remote_addr: Some(addr),
+        local_addr,
+        banner,
+        latency: Some(latency),
+        tls_info: tls,
+    }
+}
+
+/// Result for a port no address accepted; any timeout means Filtered
+pub(crate) fn unanswered(host: &str, port: u16, saw_timeout: bool) -> ScanResult {
+    ScanResult {
+        host: host.to_string(),
+        port,
+        protocol: Protocol::Tcp,
+        status: if saw_timeout {
+            PortStatus::Filtered
+        } else {
+            PortStatus::Closed
+        },
+        service: service_for(port).to_string(),
+        os_hint: None,
+        confidence: 0,
+        detail: None,
+        remote_addr: None,
+        local_addr: None,
+        banner: None,
+        latency: None,
+        tls_info: None,
+    }
+}
+
+/// Result for a port every address failed to reach, see
+/// [`unreachable_status`]
+pub(crate) fn unreached(host: &str, port: u16, kind: std::io::ErrorKind) -> ScanResult {
+    ScanResult {
+        status: unreachable_status(kind),
+        detail: Some(kind.to_string()),
+        ..unanswered(host, port, false)
+    }
+}
+
+/// Connect, send nothing, and see whether the peer resets or closes
+/// the connection without a single byte within `RESET_WINDOW_MS`.
+/// A read timeout means the service is waiting for us — it's alive.
+fn closes_after_accept(addr: SocketAddr, setup: &HostSetup) -> bool {
+    let Ok(mut s) = setup.dial(addr) else {
+        // Accepted a moment ago, refused now: same story
+        return true;
+    };
+    s.set_read_timeout(Some(Duration::from_millis(RESET_WINDOW_MS))).ok();
+
+    let mut buf = [0u8; 1];
+    match s.read(&mut buf) {
+        Ok(0) => true,
+        Ok(_) => false,
+        Err(e) => matches!(
+            e.kind(),
+            std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted
+        ),
+    }
+}
+
+/// Result for a port left unprobed because the host budget expired
+pub(crate) fn skipped(host: &str, port: u16) -> ScanResult {
+    ScanResult {
+        status: PortStatus::Skipped,
+        ..unanswered(host, port, false)
+    }
+}
+
+/// Result for a port whose tunnel the proxy refused
+pub(crate) fn proxy_denied(host: &str, port: u16) -> ScanResult {
+    ScanResult {
+        status: PortStatus::ProxyDenied,
+        ..unanswered(host, port, false)
+    }
+}
+
+// =======================
+// BANNER GRAB
+// =======================
+/// Connect to `addr` and return the printable part of the first
+/// `max_bytes` the service sends by itself; `None` if it stays silent
+/// for `timeout`
+pub fn grab_banner(addr: SocketAddr, timeout: Duration, max_bytes: usize) -> Option<String> {
+    let mut s = TcpStream::connect_timeout(&addr, timeout).ok()?;
+    read_banner(&mut s, timeout, max_bytes)
+}
+
+/// Read until `max_bytes`, EOF, or `timeout` passes in total. Line
+/// breaks and tabs become spaces, other control characters and a
+/// multi-byte character cut off at the end are dropped.
+fn read_banner(s: &mut TcpStream, timeout: Duration, max_bytes: usize) -> Option<String> {
+    let deadline = Instant::now() + timeout;
+    let mut buf = vec![0u8; max_bytes];
+    let mut len = 0;
+
+    while len < max_bytes {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() || s.set_read_timeout(Some(left)).is_err() {
+            break;
+        }
+        match s.read(&mut buf[len..]) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => len += n,
+        }
+    }
+    printable(&buf[..len])
+}
+
+/// Banner text from raw bytes; `None` when nothing printable is left
+fn printable(bytes: &[u8]) -> Option<String> {
+    let text: String = String::from_utf8_lossy(bytes)
+        .chars()
+        .filter_map(|c| match c {
+            '\r' | '\n' | '\t' => Some(' '),
+            char::REPLACEMENT_CHARACTER => None,
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+// =======================
+// UDP
+// =======================
+/// DNS query for the root's NS records (recursion desired)
+const DNS_QUERY: &[u8] = &[
+    0x17, 0x38, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x02, 0x00, 0x01,
+];
+/// SNMPv1 GetRequest for sysDescr.0, community `public`
+const SNMP_GET: &[u8] = &[
+    0x30, 0x26, 0x02, 0x01, 0x00, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c',
+    0xa0, 0x19, 0x02, 0x01, 0x01, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00,
+    0x30, 0x0e, 0x30, 0x0c, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x01, 0x00, 0x05, 0x00,
+];
+/// NTPv3 client request: LI 0, version 3, mode 3, the rest zero
+const NTP_REQUEST: [u8; 48] = {
+    let mut packet = [0u8; 48];
+    packet[0] = 0x1b;
+    packet
+};
+
+/// What one datagram got back
+enum UdpReply {
+    /// Bytes received
+    Data(usize),
+    /// ICMP port unreachable
+    Unreachable,
+    Silent,
+}
+
+/// Datagram most likely to make the service on `port` answer; empty
+/// when none is known (many services ignore it, and stay Open|Filtered)
+fn udp_payload(port: u16) -> &'static [u8] {
+    match port {
+        53 | 5353 => DNS_QUERY,
+        123 => &NTP_REQUEST,
+        161 => SNMP_GET,
+        _ => &[],
+    }
+}
+
+/// Send [`udp_payload`] to each address: any reply is Open, an ICMP
+/// port unreachable from every address is Closed, silence (after the
+/// retries) is Open|Filtered
+fn udp_scan_single(config: &ScanConfig, host: &str, setup: &HostSetup, port: u16) -> ScanResult {
+    let mut saw_silence = setup.ips.is_empty();
+
+    'addrs: for &ip in &setup.ips {
+        let addr = SocketAddr::new(ip, port);
+        for attempt in 0..=config.retries {
+            if attempt > 0 && !config.retry_delay.is_zero() {
+                thread::sleep(config.retry_delay);
+            }
+            match udp_exchange(setup, addr, udp_payload(port), config.connect_timeout()) {
+                UdpReply::Data(len) => {
+                    let service = udp_service_for(port);
+                    return ScanResult {
+                        status: PortStatus::Open,
+                        service: service.to_string(),
+                        confidence: confidence_score(service, &None),
+                        detail: Some(format!("{} byte reply", len)),
+                        remote_addr: Some(addr),
+                        ..udp_unanswered(host, port, false)
+                    };
+                }
+                UdpReply::Unreachable => continue 'addrs,
+                UdpReply::Silent => saw_silence = true,
+            }
+        }
+    }
+
+    udp_unanswered(host, port, saw_silence)
+}
+
+/// One datagram and the wait for its answer. The socket is connected
+/// (not `send_to`), so the kernel reports an ICMP port unreachable as
+/// `ConnectionRefused` on the next receive.
+fn udp_exchange(setup: &HostSetup, addr: SocketAddr, payload: &[u8], timeout: Duration) -> UdpReply {
+    let any: IpAddr = match addr {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let local = setup.interface.as_ref().and_then(|i| i.bind_addr(addr.ip())).unwrap_or(any);
+    let Ok(socket) = UdpSocket::bind(SocketAddr::new(local, 0)) else {
+        return UdpReply::Silent;
+    };
+    if socket.connect(addr).is_err()
+        || socket.set_read_timeout(Some(timeout)).is_err()
+        || socket.send(payload).is_err()
+    {
+        return UdpReply::Silent;
+    }
+
+    let mut buf = [0u8; 1500];
+    match socket.recv(&mut buf) {
+        Ok(len) => UdpReply::Data(len),
+        Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => UdpReply::Unreachable,
+        Err(_) => UdpReply::Silent,
+    }
+}
+
+/// UDP port without a reply: Open|Filtered if any address stayed
+/// silent, Closed if all of them sent a port unreachable
+fn udp_unanswered(host: &str, port: u16, silent: bool) -> ScanResult {
+    ScanResult {
+        protocol: Protocol::Udp,
+        status: if silent {
+            PortStatus::OpenFiltered
+        } else {
+            PortStatus::Closed
+        },
+        service: udp_service_for(port).to_string(),
+        ..unanswered(host, port, false)
+    }
+}
+
+// =======================
+// SERVICE DETECTION (PROBES)
+// =======================
+/// What a probe confirmed
+struct Probed {
+    /// Protocol name the OS and confidence rules know (`SSH`)
+    kind: &'static str,
+    /// The server's own identification, shown instead of `kind`
+    service: Option<String>,
+    /// Banner, version, ...
+    detail: Option<String>,
+    /// Handshake details of a TLS service
+    tls: Option<TlsInfo>,
+}
+
+impl Probed {
+    fn named(kind: &'static str, detail: Option<String>) -> Probed {
+        Probed {
+            kind,
+            service: None,
+            detail,
+            tls: None,
+        }
+    }
+}
+
+fn protocol_probe(addr: SocketAddr, setup: &HostSetup, port: u16) -> Option<Probed> {
+    match port {
+        80 | 8080 | 8000 => http_probe(addr, setup).then(|| Probed::named("HTTP", None)),
+        443 | 8443 => https_probe(addr, setup),
+        22 => ssh_probe(addr, setup).map(|banner| Probed {
+            service: Some(banner),
+            ..Probed::named("SSH", None)
+        }),
+        25 | 587 => smtp_probe(addr, setup).map(|banner| Probed::named("SMTP", Some(banner))),
+        110 => pop3_probe(addr, setup).map(|banner| Probed::named("POP3", Some(banner))),
+        143 => imap_probe(addr, setup).map(|banner| Probed::named("IMAP", Some(banner))),
+        3306 => mysql_probe(addr, setup).map(|version| Probed::named("MYSQL", Some(version))),
+        3389 => rdp_probe(addr, setup).map(|security| Probed::named("RDP", Some(security))),
+        _ => None,
+    }
+}
+
+fn http_probe(addr: SocketAddr, setup: &HostSetup) -> bool {
+    if let Ok(mut s) = setup.dial(addr) {
+        let _ = s.write_all(b"HEAD / HTTP/1.1\r\nHost: x\r\n\r\n");
+        s.set_read_timeout(Some(Duration::from_millis(PROBE_READ_MS))).ok();
+        let mut buf = [0u8; 4];
+        return s.read(&mut buf).is_ok();
+    }
+    false
+}
+
+/// Handshake details when built with `tls`; otherwise, or when the
+/// handshake fails (TLS 1.0 only, odd ciphers), the ClientHello heuristic
+fn https_probe(addr: SocketAddr, setup: &HostSetup) -> Option<Probed> {
+    #[cfg(feature = "tls")]
+    if let Some(info) = tls_handshake(addr, setup) {
+        return Some(Probed {
+            detail: info.alpn.as_ref().map(|p| format!("({})", p)),
+            tls: Some(info),
+            ..Probed::named("HTTPS", None)
+        });
+    }
+    tls_probe(addr, setup).map(|alpn| Probed::named("HTTPS", alpn.map(|p| format!("({})", p))))
+}
+
+#[cfg(feature = "tls")]
+fn tls_handshake(addr: SocketAddr, setup: &HostSetup) -> Option<TlsInfo> {
+    let mut s = setup.dial(addr).ok()?;
+    s.set_read_timeout(Some(Duration::from_millis(PROBE_READ_MS))).ok();
+    s.set_write_timeout(Some(Duration::from_millis(PROBE_READ_MS))).ok();
+    crate::core::tls::handshake(&mut s, setup.sni.as_deref(), addr.ip())
+}
+
+/// An answer to the ClientHello that starts with a TLS record counts
+/// as TLS; `Some(Some(proto))` when the ServerHello picked an ALPN
+/// protocol (`h2`, `http/1.1`). Plain-text replies and EOF are `None`.
+fn tls_probe(addr: SocketAddr, setup: &HostSetup) -> Option<Option<String>> {
+    let mut s = setup.dial(addr).ok()?;
+    s.set_read_timeout(Some(Duration::from_millis(PROBE_READ_MS))).ok();
+    let _ = s.write_all(&setup.hello);
+
+    // The ServerHello leads the server's first flight; read until it
+    // is complete (certificates may follow in the same segments)
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = match s.read(&mut chunk) {
+            Ok(n) => n,
+            Err(_) if buf.is_empty() => return None,
+            Err(_) => break,
+        };
+        buf.extend_from_slice(&chunk[..n]);
+        let complete = buf.len() >= 5 && buf.len() >= 5 + u16::from_be_bytes([buf[3], buf[4]]) as usize;
+        if n == 0 || complete || buf.len() > 16 * 1024 {
+            break;
+        }
+    }
+    is_tls_record(&buf).then(|| alpn_selected(&buf))
+}
+
+/// Handshake (0x16) or alert (0x15) record of TLS 1.x (version major
+/// 3): all a TLS server answers a ClientHello with
+fn is_tls_record(buf: &[u8]) -> bool {
+    matches!(buf, [0x16 | 0x15, 0x03, ..])
+}
+
+/// Protocol the server selected in the ALPN extension of a ServerHello
+/// record, if it sent one
+fn alpn_selected(record: &[u8]) -> Option<String> {
+    // Handshake record carrying a ServerHello
+    if record.first() != Some(&0x16) || record.get(5) != Some(&0x02) {
+        return None;
+    }
+    let hello = record.get(9..)?;
+    // version(2) + random(32), then session id, cipher(2), compression(1)
+    let session = *hello.get(34)? as usize;
+    let mut pos = 35 + session + 3;
+    let ext_len = u16::from_be_bytes([*hello.get(pos)?, *hello.get(pos + 1)?]) as usize;
+    pos += 2;
+    let end = (pos + ext_len).min(hello.len());
+
+    while pos + 4 <= end {
+        let kind = u16::from_be_bytes([hello[pos], hello[pos + 1]]);
+        let len = u16::from_be_bytes([hello[pos + 2], hello[pos + 3]]) as usize;
+        let data = hello.get(pos + 4..pos + 4 + len)?;
+        // ALPN: list length(2), then exactly one length-prefixed name
+        if kind == 0x0010 {
+            let name = data.get(3..3 + *data.get(2)? as usize)?;
+            return String::from_utf8(name.to_vec()).ok();
+        }
+        pos += 4 + len;
+    }
+    None
+}
+
+/// The identification line (`SSH-2.0-OpenSSH_9.6`) an SSH server
+/// sends first
+fn ssh_probe(addr: SocketAddr, setup: &HostSetup) -> Option<String> {
+    let s = setup.dial(addr).ok()?;
+    s.set_read_timeout(Some(Duration::from_millis(PROBE_READ_MS))).ok();
+    ssh_identification(&mut BufReader::new(&s).take(SSH_ID_MAX))
+}
+
+/// RFC 4253 caps the identification line at 255 bytes with CR LF
+const SSH_ID_MAX: u64 = 255;
+
+/// First line if it is an SSH identification, without the line end
+/// and with anything unprintable dropped
+fn ssh_identification(reader: &mut impl BufRead) -> Option<String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let line: String = line.trim_end().chars().filter(|c| !c.is_control()).collect();
+    line.starts_with("SSH-").then_some(line)
+}
+
+/// Needs a real `220` greeting. Returns the server identity
+/// (`mail.example.com ESMTP Postfix`) plus the EHLO extensions, e.g.
+/// `... [PIPELINING,SIZE,STARTTLS] (STARTTLS available)`
+fn smtp_probe(addr: SocketAddr, setup: &HostSetup) -> Option<String> {
+    let s = setup.dial(addr).ok()?;
+    s.set_read_timeout(Some(Duration::from_millis(PROBE_READ_MS))).ok();
+    let mut reader = BufReader::new(&s);
+
+    let (220, greeting) = smtp_reply(&mut reader)? else {
+        return None;
+    };
+    let mut banner = greeting.into_iter().next().unwrap_or_default();
+
+    // First EHLO line is the server's name again; the rest are extensions
+    if (&s).write_all(b"EHLO wise1738\r\n").is_ok()
+        && let Some((250, lines)) = smtp_reply(&mut reader)
+    {
+        let ext: Vec<&str> = lines
+            .iter()
+            .skip(1)
+            .filter_map(|l| l.split_whitespace().next())
+            .collect();
+        if !ext.is_empty() {
+            banner.push_str(&format!(" [{}]", ext.join(",")));
+        }
+        note_starttls(&mut banner, ext.iter().any(|e| e.eq_ignore_ascii_case("STARTTLS")));
+    }
+
+    let _ = (&s).write_all(b"QUIT\r\n");
+    Some(banner)
+}
+
+/// Needs a `* OK` greeting. Returns the greeting text, noting STARTTLS
+/// when `CAPABILITY` (or the greeting's own capability list) has it
+fn imap_probe(addr: SocketAddr, setup: &HostSetup) -> Option<String> {
+    let s = setup.dial(addr).ok()?;
+    s.set_read_timeout(Some(Duration::from_millis(PROBE_READ_MS))).ok();
+    let mut reader = BufReader::new(&s);
+
+    let mut greeting = String::new();
+    reader.read_line(&mut greeting).ok()?;
+    let mut banner = greeting.trim_end().strip_prefix("* OK")?.trim().to_string();
+
+    let mut starttls = imap_has_starttls(&banner);
+    if (&s).write_all(b"a1 CAPABILITY\r\n").is_ok()
+        && let Some(caps) = imap_capabilities(&mut reader)
+    {
+        starttls |= imap_has_starttls(&caps);
+    }
+    note_starttls(&mut banner, starttls);
+
+    let _ = (&s).write_all(b"a2 LOGOUT\r\n");
+    Some(banner)
+}
+
+/// Untagged `* CAPABILITY` lines up to the `a1` tagged reply
+fn imap_capabilities(reader: &mut impl BufRead) -> Option<String> {
+    let mut caps = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        if let Some(list) = line.strip_prefix("* CAPABILITY") {
+            caps.push_str(list.trim_end());
+        } else if line.starts_with("a1 ") {
+            return Some(caps);
+        }
+    }
+}
+
+fn imap_has_starttls(caps: &str) -> bool {
+    caps.split(|c: char| c.is_whitespace() || c == '[' || c == ']')
+        .any(|t| t.eq_ignore_ascii_case("STARTTLS"))
+}
+
+/// Needs a `+OK` greeting. Returns the greeting text, noting STARTTLS
+/// when `CAPA` lists `STLS`
+fn pop3_probe(addr: SocketAddr, setup: &HostSetup) -> Option<String> {
+    let s = setup.dial(addr).ok()?;
+    s.set_read_timeout(Some(Duration::from_millis(PROBE_READ_MS))).ok();
+    let mut reader = BufReader::new(&s);
+
+    let mut greeting = String::new();
+    reader.read_line(&mut greeting).ok()?;
+    let mut banner = greeting.trim_end().strip_prefix("+OK")?.trim().to_string();
+
+    if (&s).write_all(b"CAPA\r\n").is_ok()
+        && let Some(caps) = pop3_capabilities(&mut reader)
+    {
+        note_starttls(&mut banner, caps.iter().any(|c| c.eq_ignore_ascii_case("STLS")));
+    }
+
+    let _ = (&s).write_all(b"QUIT\r\n");
+    Some(banner)
+}
+
+/// `CAPA` reply: `+OK`, one capability per line, ended by `.`
+fn pop3_capabilities(reader: &mut impl BufRead) -> Option<Vec<String>> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    if !line.starts_with("+OK") {
+        return None;
+    }
+
+    let mut caps = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        match line.trim_end() {
+            "." => return Some(caps),
+            cap => caps.push(cap.split_whitespace().next().unwrap_or("").to_string()),
+        }
+    }
+}
+
+/// Mail probes flag an upgradeable plaintext session the same way
+fn note_starttls(banner: &mut String, starttls: bool) {
+    if starttls {
+        banner.push_str(" (STARTTLS available)");
+    }
+}
+
+/// One SMTP reply, following `250-` continuation lines: code + texts
+fn smtp_reply(reader: &mut impl BufRead) -> Option<(u16, Vec<String>)> {
+    let mut texts = Vec::new();
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        let code = line.get(..3)?.parse().ok()?;
+        texts.push(line.get(4..).unwrap_or("").to_string());
+
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Some((code, texts));
+        }
+    }
+}
+
+/// Server version (`8.0.36`) from a valid initial handshake packet
+fn mysql_probe(addr: SocketAddr, setup: &HostSetup) -> Option<String> {
+    let mut s = setup.dial(addr).ok()?;
+    s.set_read_timeout(Some(Duration::from_millis(PROBE_READ_MS))).ok();
+    let mut buf = [0u8; 128];
+    let n = s.read(&mut buf).ok()?;
+    mysql_handshake_version(&buf[..n])
+}
+
+/// Packet header (3-byte length, sequence 0), protocol version 10,
+/// then a NUL-terminated printable version string
+fn mysql_handshake_version(packet: &[u8]) -> Option<String> {
+    let (header, payload) = packet.split_at_checked(4)?;
+    let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+    if header[3] != 0 || !(2..=1024).contains(&len) {
+        return None;
+    }
+
+    let (&10, rest) = payload.split_first()? else {
+        return None;
+    };
+    let end = rest.iter().position(|&b| b == 0)?;
+    let version = &rest[..end];
+    if version.is_empty() || !version.iter().all(|b| b.is_ascii_graphic()) {
+        return None;
+    }
+    String::from_utf8(version.to_vec()).ok()
+}
+
+/// X.224 Connection Request asking for TLS + CredSSP; the Connection
+/// Confirm tells us which security layer the server wants
+fn rdp_probe(addr: SocketAddr, setup: &HostSetup) -> Option<String> {
+    let mut s = setup.dial(addr).ok()?;
+    s.set_read_timeout(Some(Duration::from_millis(PROBE_READ_MS))).ok();
+    s.write_all(&RDP_CONNECTION_REQUEST).ok()?;
+
+    let mut buf = [0u8; 32];
+    let n = s.read(&mut buf).ok()?;
+    rdp_security(&buf[..n])
+}
+
+/// TPKT (4) + X.224 CR (7) + RDP_NEG_REQ for PROTOCOL_SSL | PROTOCOL_HYBRID
+const RDP_CONNECTION_REQUEST: [u8; 19] = [
+    0x03, 0x00, 0x00, 0x13,
+    0x0e, 0xe0, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x08, 0x00, 0x03, 0x00, 0x00, 0x00,
+];
+
+/// Security layer from an X.224 Connection Confirm, `None` if it isn't one
+fn rdp_security(resp: &[u8]) -> Option<String> {
+    // TPKT version 3, then the CC TPDU code (0xd0)
+    if resp.len() < 11 || resp[..2] != [0x03, 0x00] || resp[5] & 0xf0 != 0xd0 {
+        return None;
+    }
+
+    // Old servers answer without a negotiation block
+    let Some(neg) = resp.get(11..19) else {
+        return Some("standard RDP security".into());
+    };
+    let value = u32::from_le_bytes([neg[4], neg[5], neg[6], neg[7]]);
+
+    Some(match (neg[0], value) {
+        (0x02, 0) => "standard RDP security".into(),
+        (0x02, 1) => "TLS".into(),
+        (0x02, 2 | 8) => "CredSSP (NLA) required".into(),
+        (0x02, p) => format!("protocol {:#x}", p),
+        (0x03, 5) => "CredSSP (NLA) required".into(),
+        (0x03, code) => format!("negotiation failed ({})", code),
+        _ => return None,
+    })
+}
+
+// =======================
+// OS SIGNAL
+// =======================
+fn os_detect_signal(port: u16, service: &str) -> Option<&'static str> {
+    match (port, service) {
+        (445, "SMB") | (3389, "RDP") => Some("Windows"),
+        (22, "SSH") => Some("Unix-like"),
+        (80, "HTTP") | (443, "HTTPS") => Some("Unix-like"),
+        _ => None,
+    }
+}
+
+// =======================
+// CONFIDENCE SCORE
+// =======================
+fn confidence_score(service: &str, os: &Option<&str>) -> u8 {
+    match (service, os) {
+        ("SSH", Some(_)) => 95,
+        ("HTTP", Some(_)) => 90,
+        ("HTTPS", Some(_)) => 90,
+        ("RDP", Some("Windows")) => 95,
+        ("SMTP", _) => 85,
+        ("MYSQL", _) => 85,
+        (NO_SERVICE, _) => 10,
+        ("unknown", _) => 30,
+        _ => 60,
+    }
+}
+
+// =======================
+// TLS CLIENT HELLO (FULL, TLS 1.2)
+// =======================
+/// Built once per host ([`HostSetup`]); `sni` adds a server_name
+/// extension so name-based virtual hosts answer for the right site
+fn tls_client_hello(sni: Option<&str>) -> Vec<u8> {
+    let mut extensions = vec![
+        // supported_groups: x25519, secp256r1, secp384r1
+        0x00, 0x0a, 0x00, 0x08, 0x00, 0x06, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x18,
+        // ec_point_formats: uncompressed
+        0x00, 0x0b, 0x00, 0x02, 0x01, 0x00,
+        // signature_algorithms: ECDSA / RSA-PSS / RSA PKCS#1 with SHA-256..512
+        0x00, 0x0d, 0x00, 0x10, 0x00, 0x0e,
+        0x04, 0x03, 0x08, 0x04, 0x04, 0x01, 0x05, 0x03, 0x08, 0x05, 0x05, 0x01, 0x06, 0x01,
+        // ALPN: h2, http/1.1
+        0x00, 0x10, 0x00, 0x0e, 0x00, 0x0c,
+        0x02, b'h', b'2',
+        0x08, b'h', b't', b't', b'p', b'/', b'1', b'.', b'1',
+    ];
+    if let Some(name) = sni.filter(|n| n.len() <= 0xff00) {
+        let len = name.len() as u16;
+        extensions.extend_from_slice(&[0x00, 0x00]);
+        extensions.extend_from_slice(&(len + 5).to_be_bytes());
+        extensions.extend_from_slice(&(len + 3).to_be_bytes());
+        extensions.push(0x00); // host_name
+        extensions.extend_from_slice(&len.to_be_bytes());
+        extensions.extend_from_slice(name.as_bytes());
+    }
+
+    let mut body = vec![0x03, 0x03];
+    body.extend_from_slice(b"SCORE\x00\x01\x02");
+    body.extend(0x03..=0x1a_u8);
+    body.push(0x00); // no session id
+    // ECDHE GCM / ChaCha20 suites modern servers insist on, then the
+    // old RSA ones
+    body.extend_from_slice(&[
+        0x00, 0x16,
+        0xc0, 0x2b, 0xc0, 0x2f, 0xc0, 0x2c, 0xc0, 0x30, 0xcc, 0xa9, 0xcc, 0xa8,
+        0xc0, 0x13, 0xc0, 0x14, 0x00, 0x2f, 0x00, 0x35, 0x00, 0x0a,
+    ]);
+    body.extend_from_slice(&[0x01, 0x00]);
+    body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    body.extend_from_slice(&extensions);
+
+    let mut hello = vec![0x16, 0x03, 0x01];
+    hello.extend_from_slice(&(body.len() as u16 + 4).to_be_bytes());
+    hello.push(0x01); // ClientHello
+    hello.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    hello.extend_from_slice(&body);
+    hello
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn localhost() -> HostSetup {
+        HostSetup::new("127.0.0.1", vec![IpAddr::V4(std::net::Ipv4Addr::LOCALHOST)], &ScanConfig::default())
+    }
+
+    /// Answers by port number: 1xxx open, 2xxx timeout, anything else refused
+    struct Scripted;
+
+    impl Connector for Scripted {
+        fn connect(&self, addr: SocketAddr, _timeout: Duration) -> TcpResult {
+            match addr.port() {
+                1000..=1999 => TcpResult::Open(None),
+                2000..=2999 => TcpResult::Timeout,
+                _ => TcpResult::Refused,
+            }
+        }
+    }
+
+    fn status(port: u16) -> PortStatus {
+        scan_single(&Scripted, &ScanConfig::default(), "127.0.0.1", &localhost(), port).status
+    }
+
+    #[test]
+    fn classifies_connect_outcomes() {
+        assert_eq!(status(1234), PortStatus::Open);
+        assert_eq!(status(2345), PortStatus::Filtered);
+        assert_eq!(status(3456), PortStatus::Closed);
+    }
+
+    /// Fails every connect with the OS error of the given kind
+    struct Failing(std::io::ErrorKind);
+
+    impl Connector for Failing {
+        fn connect(&self, _addr: SocketAddr, _timeout: Duration) -> TcpResult {
+            classify_error(&std::io::Error::from(self.0))
+        }
+    }
+
+    #[test]
+    fn connect_errors_map_to_their_status() {
+        use std::io::ErrorKind::*;
+        let expected = [
+            (ConnectionRefused, PortStatus::Closed),
+            (TimedOut, PortStatus::Filtered),
+            (NetworkUnreachable, PortStatus::Unreachable),
+            (HostUnreachable, PortStatus::Unreachable),
+            (PermissionDenied, PortStatus::Filtered),
+            (AddrNotAvailable, PortStatus::Filtered),
+            (ConnectionReset, PortStatus::Filtered),
+        ];
+        for (kind, want) in expected {
+            let r = scan_single(&Failing(kind), &ScanConfig::default(), "127.0.0.1", &localhost(), 80);
+            assert_eq!(r.status, want, "{:?}", kind);
+        }
+    }
+
+    /// First address has no route, the second refuses
+    struct HalfRouted;
+
+    impl Connector for HalfRouted {
+        fn connect(&self, addr: SocketAddr, _timeout: Duration) -> TcpResult {
+            match addr {
+                SocketAddr::V6(_) => TcpResult::Unreachable(std::io::ErrorKind::NetworkUnreachable),
+                SocketAddr::V4(_) => TcpResult::Refused,
+            }
+        }
+    }
+
+    #[test]
+    fn one_answering_address_beats_an_unreachable_one() {
+        let ips = vec!["2001:db8::1".parse().unwrap(), IpAddr::V4(Ipv4Addr::LOCALHOST)];
+        let setup = HostSetup::new("dual.example", ips, &ScanConfig::default());
+
+        let r = scan_single(&HalfRouted, &ScanConfig::default(), "dual.example", &setup, 80);
+        assert_eq!(r.status, PortStatus::Closed);
+
+        let v6_only = HostSetup::new("dual.example", vec!["2001:db8::1".parse().unwrap()], &ScanConfig::default());
+        let r = scan_single(&HalfRouted, &ScanConfig::default(), "dual.example", &v6_only, 80);
+        assert_eq!(r.status, PortStatus::Unreachable);
+        assert_eq!(r.status.to_string(), "Unreachable");
+    }
+
+    #[test]
+    fn expired_host_budget_skips_ports() {
+        let config = ScanConfig {
+            host_timeout: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        let ports = Ports::multiple(vec![1000, 2000, 3000]);
+
+        let results = scan_with(&Target::new("127.0.0.1"), &ports, &config, &Scripted);
+
+        assert!(results.iter().all(|r| r.status == PortStatus::Skipped));
+    }
+
+    #[test]
+    fn scan_with_collects_every_port_sorted() {
+        let target = Target::new("127.0.0.1");
+        let ports = Ports::multiple(vec![3000, 2000, 1000]);
+
+        let results = scan_with(&target, &ports, &ScanConfig::default(), &Scripted);
+        let got: Vec<_> = results.iter().map(|r| (r.port, r.status)).collect();
+
+        assert_eq!(
+            got,
+            vec![
+                (1000, PortStatus::Open),
+                (2000, PortStatus::Filtered),
+                (3000, PortStatus::Closed),
+            ]
+        );
+    }
+
+    /// Counts results instead of storing them
+    #[derive(Default)]
+    struct Counting {
+        open: usize,
+        other: usize,
+    }
+
+    impl ResultSink for Counting {
+        fn on_result(&mut self, result: &ScanResult) {
+            match result.status {
+                PortStatus::Open => self.open += 1,
+                _ => self.other += 1,
+            }
+        }
+    }
+
+    #[test]
+    fn custom_sink_sees_filtered_stream() {
+        let target = Target::new("127.0.0.1");
+        let ports = Ports::multiple(vec![1000, 1001, 2000, 3000]);
+        let config = ScanConfig {
+            connect_only: true,
+            open_only: true,
+            ..Default::default()
+        };
+
+        let mut sink = Counting::default();
+        scan_with_sink(&target, &ports, &config, &Scripted, &mut sink).unwrap();
+
+        assert_eq!((sink.open, sink.other), (2, 0));
+    }
+
+    #[test]
+    fn reads_multiline_smtp_reply() {
+        let mut reply: &[u8] = b"250-mx.example.com\r\n250-PIPELINING\r\n250 STARTTLS\r\nNEXT";
+
+        let (code, texts) = smtp_reply(&mut reply).unwrap();
+
+        assert_eq!(code, 250);
+        assert_eq!(texts, vec!["mx.example.com", "PIPELINING", "STARTTLS"]);
+    }
+
+    #[test]
+    fn rejects_non_smtp_greeting() {
+        let mut reply: &[u8] = b"SSH-2.0-OpenSSH_9.6\r\n";
+
+        assert_eq!(smtp_reply(&mut reply), None);
+    }
+
+    #[test]
+    fn parses_mysql_handshake_version() {
+        let mut packet = vec![0x4a, 0x00, 0x00, 0x00, 0x0a];
+        packet.extend_from_slice(b"8.0.36\0");
+        packet.extend_from_slice(&[0x01, 0x00, 0x00, 0x00]);
+
+        assert_eq!(mysql_handshake_version(&packet).as_deref(), Some("8.0.36"));
+    }
+
+    #[test]
+    fn reads_ssh_identification_line() {
+        let id = |bytes: &[u8]| ssh_identification(&mut &bytes[..]);
+
+        assert_eq!(id(b"SSH-2.0-OpenSSH_9.6\r\n").as_deref(), Some("SSH-2.0-OpenSSH_9.6"));
+        assert_eq!(id(b"SSH-2.0-dropbear\x1b[2J\r\n").as_deref(), Some("SSH-2.0-dropbear[2J"));
+        assert_eq!(id(b"220 mail ESMTP\r\n"), None);
+        assert_eq!(id(b""), None);
+    }
+
+    #[test]
+    fn rejects_non_mysql_banners() {
+        assert_eq!(mysql_handshake_version(b"SSH-2.0-OpenSSH_9.6\r\n"), None);
+        assert_eq!(mysql_handshake_version(b"220 mail ESMTP\r\n"), None);
+        assert_eq!(mysql_handshake_version(&[0x01]), None);
+    }
+
+    #[test]
+    fn reads_rdp_connection_confirm() {
+        let cc = |kind: u8, value: u8| {
+            vec![
+                0x03, 0x00, 0x00, 0x13, 0x0e, 0xd0, 0x00, 0x00, 0x12, 0x34, 0x00,
+                kind, 0x00, 0x08, 0x00, value, 0x00, 0x00, 0x00,
+            ]
+        };
+
+        assert_eq!(rdp_security(&cc(0x02, 1)).as_deref(), Some("TLS"));
+        assert_eq!(rdp_security(&cc(0x02, 2)).as_deref(), Some("CredSSP (NLA) required"));
+        assert_eq!(rdp_security(&cc(0x03, 5)).as_deref(), Some("CredSSP (NLA) required"));
+        assert_eq!(rdp_security(&cc(0x02, 1)[..11]).as_deref(), Some("standard RDP security"));
+        assert_eq!(rdp_security(b"HTTP/1.1 400 Bad Request\r\n"), None);
+    }
+
+    #[test]
+    fn sort_keeps_hosts_grouped() {
+        let result = |host, port| unanswered(host, port, false);
+        let mut results = vec![
+            result("10.0.0.2", 443),
+            result("10.0.0.1", 80),
+            result("10.0.0.2", 22),
+            result("10.0.0.1", 22),
+            result("10.0.0.2", 80),
+        ];
+
+        sort_results(&mut results);
+        let got: Vec<_> = results.iter().map(|r| (r.host.as_str(), r.port)).collect();
+
+        assert_eq!(
+            got,
+            vec![
+                ("10.0.0.2", 22),
+                ("10.0.0.2", 80),
+                ("10.0.0.2", 443),
+                ("10.0.0.1", 22),
+                ("10.0.0.1", 80),
+            ]
+        );
+    }
+
+    /// Panics on port 4242, behaves like [`Scripted`] otherwise
+    struct Buggy;
+
+    impl Connector for Buggy {
+        fn connect(&self, addr: SocketAddr, timeout: Duration) -> TcpResult {
+            assert_ne!(addr.port(), 4242, "simulated probe bug");
+            Scripted.connect(addr, timeout)
+        }
+    }
+
+    #[test]
+    fn worker_panic_keeps_partial_results() {
+        let target = Target::new("127.0.0.1");
+        let ports = Ports::multiple(vec![3000, 3001, 4242]);
+
+        let mut results = Vec::new();
+        let outcome = scan_with_sink(&target, &ports, &ScanConfig::default(), &Buggy, &mut results);
+
+        assert_eq!(outcome, Err(ScanError::WorkerPanicked { workers: 1 }));
+        assert_eq!(results.len(), 2);
+    }
+
+    /// Counts connect attempts, answering like [`Scripted`]
+    #[derive(Default)]
+    struct Attempts(std::sync::atomic::AtomicUsize);
+
+    impl Connector for Attempts {
+        fn connect(&self, addr: SocketAddr, timeout: Duration) -> TcpResult {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Scripted.connect(addr, timeout)
+        }
+    }
+
+    #[test]
+    fn retries_repeat_timeouts_only() {
+        let config = ScanConfig {
+            retries: 2,
+            ..Default::default()
+        };
+        let attempts = |port| {
+            let connector = Attempts::default();
+            scan_single(&connector, &config, "127.0.0.1", &localhost(), port);
+            connector.0.into_inner()
+        };
+
+        assert_eq!(attempts(2000), 3);
+        assert_eq!(attempts(3000), 1);
+    }
+
+    /// Nameserver on loopback that counts A queries: `db.test` is
+    /// 127.0.0.1, every other name has no records
+    fn counting_nameserver() -> (SocketAddr, Arc<AtomicU64>) {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let addr = socket.local_addr().unwrap();
+        let lookups = Arc::new(AtomicU64::new(0));
+        let counter = lookups.clone();
+
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            while let Ok((n, peer)) = socket.recv_from(&mut buf) {
+                let mut reply = buf[..n].to_vec();
+                reply[2] |= 0x80;
+                let is_a = reply[n - 4..n - 2] == [0, 1];
+                if is_a {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                }
+                if is_a && reply[12..].starts_with(b"\x02db\x04test\x00") {
+                    reply[7] = 1;
+                    reply.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 127, 0, 0, 1]);
+                }
+                let _ = socket.send_to(&reply, peer);
+            }
+        });
+        (addr, lookups)
+    }
+
+    #[test]
+    fn host_is_resolved_once_per_scan() {
+        let (server, lookups) = counting_nameserver();
+        let config = ScanConfig {
+            resolver: Some(server),
+            workers: Some(8),
+            ..Default::default()
+        };
+
+        let results = scan_with(&Target::new("db.test"), &Ports::range(3000, 3199), &config, &Scripted);
+
+        assert_eq!(results.len(), 200);
+        assert!(results.iter().all(|r| r.status == PortStatus::Closed));
+        assert_eq!(lookups.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn unresolved_host_is_one_error() {
+        let (server, _) = counting_nameserver();
+        let config = ScanConfig {
+            resolver: Some(server),
+            ..Default::default()
+        };
+        let mut results = Vec::new();
+
+        let outcome = scan_with_sink(&Target::new("nx.test"), &Ports::range(3000, 3099), &config, &Scripted, &mut results);
+
+        assert!(matches!(outcome, Err(ScanError::Unresolved { ref host, .. }) if host == "nx.test"), "{:?}", outcome);
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn rate_cap_spreads_the_scan_out() {
+        let config = ScanConfig {
+            max_rate: Some(50),
+            workers: Some(16),
+            ..Default::default()
+        };
+        let ports = Ports::range(3000, 3099);
+
+        let started = Instant::now();
+        let results = scan_with(&Target::new("127.0.0.1"), &ports, &config, &Scripted);
+        let took = started.elapsed();
+
+        assert_eq!(results.len(), 100);
+        // 100 starts 20ms apart: the last one at 1.98s
+        assert!(took >= Duration::from_millis(1950), "{:?}", took);
+        assert!(took < Duration::from_secs(4), "{:?}", took);
+    }
+
+    /// Times out until the given attempt, then accepts (a flaky link)
+    struct OpensOnAttempt(usize, std::sync::atomic::AtomicUsize);
+
+    impl Connector for OpensOnAttempt {
+        fn connect(&self, _addr: SocketAddr, _timeout: Duration) -> TcpResult {
+            match self.1.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1 >= self.0 {
+                true => TcpResult::Open(None),
+                false => TcpResult::Timeout,
+            }
+        }
+    }
+
+    #[test]
+    fn port_answering_a_retry_is_open() {
+        let scan = |retries| {
+            let config = ScanConfig {
+                retries,
+                connect_only: true,
+                ..Default::default()
+            };
+            let connector = OpensOnAttempt(3, Default::default());
+            scan_single(&connector, &config, "127.0.0.1", &localhost(), 80).status
+        };
+
+        assert_eq!(scan(2), PortStatus::Open);
+        assert_eq!(scan(1), PortStatus::Filtered);
+    }
+
+    #[test]
+    fn profiles_preset_config() {
+        // (connect_only, banners, verbose, timeout ms, retries)
+        let fields = |name| {
+            let c = ScanConfig::profile(name).unwrap();
+            (c.connect_only, c.banners, c.verbose, c.connect_timeout().as_millis(), c.retries)
+        };
+        assert_eq!(fields("quick"), (true, false, false, 300, 0));
+        assert_eq!(fields("default"), (false, true, false, 700, 0));
+        assert_eq!(fields("thorough"), (false, true, true, 1500, 1));
+
+        assert_eq!(Profile::Quick.ports(), Ports::top_basic());
+        assert_eq!(Profile::Default.ports().ports.len(), 1000);
+        assert_eq!(Profile::Thorough.ports().ports.len(), 65535);
+        assert!(ScanConfig::profile("nope").is_none());
+    }
+
+    #[test]
+    fn collector_cap_stops_the_scan() {
+        let target = Target::new("127.0.0.1");
+        let ports = Ports::range(3000, 3200);
+
+        let mut collector = Collector::new(5);
+        let outcome = scan_with_sink(&target, &ports, &ScanConfig::default(), &Scripted, &mut collector);
+
+        assert_eq!(outcome, Err(ScanError::ResultLimit));
+        assert_eq!(collector.results.len(), 5);
+    }
+
+    #[test]
+    fn metrics_count_connects_and_outcomes() {
+        let config = ScanConfig {
+            retries: 1,
+            connect_only: true,
+            ..Default::default()
+        };
+        let ports = Ports::multiple(vec![1000, 2000, 3000]);
+
+        let mut metrics = ScanMetrics::default();
+        scan_threads(&Target::new("127.0.0.1"), &ports, &config, &Scripted, &mut Vec::new(), &mut metrics)
+            .unwrap();
+
+        assert_eq!((metrics.ports, metrics.open, metrics.closed, metrics.filtered), (3, 1, 1, 1));
+        // 2000 timed out twice (one retry)
+        assert_eq!((metrics.connects, metrics.timeouts), (4, 2));
+    }
+
+    #[test]
+    fn hints_when_mostly_filtered() {
+        let metrics = |filtered, closed| ScanMetrics {
+            filtered,
+            closed,
+            ..Default::default()
+        };
+        let config = ScanConfig::default();
+
+        let hint = metrics(9, 1).filtered_hint(&config).unwrap();
+        assert_eq!(hint, "90% of ports filtered (firewall, or timeout too short): try --timeout 1400ms or --retries 1");
+        assert!(metrics(8, 2).filtered_hint(&config).is_none());
+        assert!(metrics(5, 0).filtered_hint(&config).is_none());
+    }
+
+    #[test]
+    fn client_hello_lengths_and_sni() {
+        let check = |hello: &[u8]| {
+            let record = u16::from_be_bytes([hello[3], hello[4]]) as usize;
+            let handshake = u32::from_be_bytes([0, hello[6], hello[7], hello[8]]) as usize;
+            assert_eq!(record, hello.len() - 5);
+            assert_eq!(handshake, hello.len() - 9);
+        };
+
+        let plain = tls_client_hello(None);
+        let named = tls_client_hello(Some("example.com"));
+        check(&plain);
+        check(&named);
+        assert_eq!(named.len(), plain.len() + 9 + "example.com".len());
+        assert!(named.ends_with(b"\x00\x0bexample.com"));
+        assert_eq!(HostSetup::new("10.0.0.1", Vec::new(), &ScanConfig::default()).hello, plain);
+    }
+
+    /// Port 1xxx answers only given 300ms or more; 2xxx never answers
+    struct Slow;
+
+    impl Connector for Slow {
+        fn connect(&self, addr: SocketAddr, timeout: Duration) -> TcpResult {
+            match addr.port() {
+                1000..=1999 if timeout >= Duration::from_millis(300) => TcpResult::Open(None),
+                1000..=2999 => TcpResult::Timeout,
+                _ => TcpResult::Refused,
+            }
+        }
+    }
+
+    #[test]
+    fn fast_mode_verifies_timeouts() {
+        let config = ScanConfig {
+            fast: true,
+            connect_only: true,
+            ..Default::default()
+        };
+        let ports = Ports::multiple(vec![1000, 2000, 3000]);
+        let mut results = Vec::new();
+
+        scan_with_sink(&Target::new("127.0.0.1"), &ports, &config, &Slow, &mut results).unwrap();
+        sort_results(&mut results);
+
+        let statuses: Vec<PortStatus> = results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, vec![PortStatus::Open, PortStatus::Filtered, PortStatus::Closed]);
+    }
+
+    #[test]
+    fn retry_delay_spaces_attempts() {
+        let config = ScanConfig {
+            retries: 2,
+            retry_delay: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let timed = |port| {
+            let started = Instant::now();
+            scan_single(&Scripted, &config, "127.0.0.1", &localhost(), port);
+            started.elapsed()
+        };
+
+        assert!(timed(2000) >= Duration::from_millis(100));
+        assert!(timed(3000) < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn reads_alpn_from_server_hello() {
+        let mut hello = vec![0x03, 0x03];
+        hello.extend([7u8; 32]);
+        hello.extend([0x00, 0xc0, 0x2f, 0x00]); // no session id, cipher, compression
+        let extensions = [
+            0xff, 0x01, 0x00, 0x01, 0x00, // renegotiation_info
+            0x00, 0x10, 0x00, 0x05, 0x00, 0x03, 0x02, b'h', b'2',
+        ];
+        hello.extend((extensions.len() as u16).to_be_bytes());
+        hello.extend(extensions);
+
+        let mut record = vec![0x16, 0x03, 0x03];
+        record.extend((hello.len() as u16 + 4).to_be_bytes());
+        record.extend([0x02, 0x00]);
+        record.extend((hello.len() as u16).to_be_bytes());
+        record.extend(&hello);
+
+        assert_eq!(alpn_selected(&record).as_deref(), Some("h2"));
+        // Handshake alert instead of a ServerHello: TLS, but no ALPN
+        assert_eq!(alpn_selected(&[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x28]), None);
+    }
+
+    #[test]
+    fn only_a_tls_reply_counts_as_https() {
+        // Reads the probe's hello, sends `reply` and hangs up, as often
+        // as it is dialed (the tls feature dials twice)
+        let serve = |reply: &'static [u8]| {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            std::thread::spawn(move || {
+                for mut s in listener.incoming().flatten() {
+                    let _ = s.read(&mut [0u8; 4096]);
+                    let _ = s.write_all(reply);
+                }
+            });
+            addr
+        };
+
+        assert_eq!(tls_probe(serve(b"HTTP/1.1 400 Bad Request\r\n\r\n"), &localhost()), None);
+        assert_eq!(tls_probe(serve(b""), &localhost()), None);
+        assert_eq!(tls_probe(serve(&[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x28]), &localhost()), Some(None));
+        assert!(https_probe(serve(b"SSH-2.0-OpenSSH_9.6\r\n"), &localhost()).is_none());
+        assert!(https_probe(serve(b""), &localhost()).is_none());
+    }
+
+    #[test]
+    fn finds_starttls_in_imap_and_pop3_capabilities() {
+        let mut imap = "* CAPABILITY IMAP4rev1 STARTTLS LOGINDISABLED\r\na1 OK done\r\n".as_bytes();
+        assert!(imap_has_starttls(&imap_capabilities(&mut imap).unwrap()));
+        assert!(imap_has_starttls("[CAPABILITY IMAP4rev1 STARTTLS] Dovecot ready."));
+        assert!(!imap_has_starttls("[CAPABILITY IMAP4rev1 AUTH=PLAIN] ready"));
+
+        let mut pop3 = "+OK Capability list follows\r\nTOP\r\nSTLS\r\nSASL PLAIN\r\n.\r\n".as_bytes();
+        assert_eq!(pop3_capabilities(&mut pop3).unwrap(), vec!["TOP", "STLS", "SASL"]);
+        let mut refused = "-ERR unknown command\r\n".as_bytes();
+        assert_eq!(pop3_capabilities(&mut refused), None);
+    }
+
+    #[test]
+    fn verdicts_follow_the_host_pattern() {
+        let results = |host: &str, answered: u16, filtered: u16| {
+            (0..answered)
+                .map(|p| unanswered(host, 3000 + p, false))
+                .chain((0..filtered).map(|p| unanswered(host, 2000 + p, true)))
+                .collect::<Vec<_>>()
+        };
+        let mut all = [
+            results("rules", 30, 2),
+            results("drop", 1, 20),
+            results("down", 0, 5),
+            results("up", 3, 0),
+            results("mixed", 5, 5),
+        ]
+        .concat();
+
+        let verdicts: Vec<HostVerdict> = host_verdicts(&mut all).into_iter().map(|v| v.1).collect();
+
+        use HostVerdict::*;
+        assert_eq!(verdicts, vec![RuleFiltered, DefaultDrop, Down, Up, Mixed]);
+        let note = |host: &str| all.iter().find(|r| r.host == host && r.status == PortStatus::Filtered).unwrap().detail.clone();
+        assert_eq!(note("rules").as_deref(), Some("filtered by a firewall rule"));
+        assert_eq!(note("drop"), None);
+    }
+
+    #[test]
+    fn open_changes_lists_opened_and_closed_ports() {
+        let open = |host: &str, port: u16| ScanResult {
+            status: PortStatus::Open,
+            ..unanswered(host, port, false)
+        };
+        let before = [open("a", 22), open("a", 80), unanswered("a", 443, true)];
+        let after = [open("a", 22), unanswered("a", 80, false), open("a", 443), open("b", 22)];
+
+        let (opened, closed) = open_changes(&before, &after);
+
+        assert_eq!(opened, vec![("a".to_string(), 443), ("b".to_string(), 22)]);
+        assert_eq!(closed, vec![("a".to_string(), 80)]);
+        assert_eq!(open_changes(&after, &after), (vec![], vec![]));
+    }
+
+    #[test]
+    fn estimates_hops_from_ttl() {
+        let info = |ttl| HostInfo { host: "h".into(), ttl };
+        assert_eq!((info(52).initial_ttl(), info(52).hops()), (64, 12));
+        assert_eq!((info(116).initial_ttl(), info(116).hops()), (128, 12));
+        assert_eq!((info(250).initial_ttl(), info(250).hops()), (255, 5));
+        assert_eq!(info(64).to_string(), "ttl 64, ~0 hops (initial 64, Unix-like)");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn measures_loopback_ttl() {
+        let port = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let config = ScanConfig {
+            verbose: true,
+            ..ScanConfig::default()
+        };
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+
+        let info = host_info(&config, &localhost(), "127.0.0.1", addr).expect("IP_MINTTL on loopback");
+        // Nothing routes in between, whatever the kernel's default TTL
+        assert_eq!(info.hops(), 0);
+        assert_eq!(host_info(&ScanConfig::default(), &localhost(), "127.0.0.1", addr), None);
+    }
+
+    #[test]
+    fn paused_scan_waits_for_resume() {
+        let target = Target::new("127.0.0.1");
+        let ports = Ports::multiple(vec![3000, 3001, 4242]);
+        let config = ScanConfig::default();
+        config.pause.pause();
+
+        let connector = Attempts::default();
+        let results = thread::scope(|s| {
+            let scan = s.spawn(|| scan_with(&target, &ports, &config, &connector));
+            thread::sleep(PAUSE_POLL * 4);
+            assert_eq!(connector.0.load(Ordering::Relaxed), 0);
+            config.pause.resume();
+            scan.join().unwrap()
+        });
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(connector.0.load(Ordering::Relaxed), 3);
+    }
+
+    /// Records which threads did the connects
+    #[derive(Default)]
+    struct Threads(std::sync::Mutex<std::collections::HashSet<thread::ThreadId>>);
+
+    impl Connector for Threads {
+        fn connect(&self, addr: SocketAddr, timeout: Duration) -> TcpResult {
+            self.0.lock().unwrap().insert(thread::current().id());
+            Scripted.connect(addr, timeout)
+        }
+    }
+
+    #[test]
+    fn spawns_one_thread_per_batch_of_workers_ports() {
+        let target = Target::new("127.0.0.1");
+        let ports = Ports::range(3000, 3199);
+        let threads = |workers| {
+            let config = ScanConfig {
+                workers,
+                ..Default::default()
+            };
+            let connector = Threads::default();
+            scan_with(&target, &ports, &config, &connector);
+            let spawned = connector.0.lock().unwrap().len();
+            assert_eq!(spawned, config.effective_workers(&ports));
+            spawned
+        };
+
+        // 200 ports: ceil(200 / workers) threads, 64 ports each by default
+        assert_eq!(threads(None), 4);
+        assert_eq!(threads(Some(3)), 67);
+        assert_eq!(threads(Some(50)), 4);
+        assert_eq!(threads(Some(200)), 1);
+        assert_eq!(threads(Some(500)), 1);
+        // 0 is clamped to 1: one port per thread
+        assert_eq!(threads(Some(0)), 200);
+    }
+
+    #[test]
+    fn banner_keeps_printable_text() {
+        assert_eq!(printable(b"220 mail ESMTP\r\n\x00\x07ready\r\n").as_deref(), Some("220 mail ESMTP ready"));
+        // `é` cut in half by the byte limit
+        assert_eq!(printable(b"caf\xc3").as_deref(), Some("caf"));
+        assert_eq!(printable(b"\x00\x01\r\n"), None);
+    }
+
+    #[test]
+    fn silent_service_gives_no_banner() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let talker = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let talker_addr = talker.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut s, _) = talker.accept().unwrap();
+            s.write_all(b"SSH-2.0-test\r\n").unwrap();
+            thread::sleep(Duration::from_millis(500));
+        });
+
+        let started = Instant::now();
+        assert_eq!(grab_banner(addr, Duration::from_millis(200), BANNER_BYTES), None);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(grab_banner(talker_addr, Duration::from_millis(200), 8).as_deref(), Some("SSH-2.0-"));
+    }
+
+    #[test]
+    fn progress_counts_each_final_result_once() {
+        let (tx, rx) = mpsc::channel();
+        let config = ScanConfig {
+            fast: true,
+            connect_only: true,
+            open_only: true,
+            progress: Some(tx),
+            ..Default::default()
+        };
+        let ports = Ports::multiple(vec![1000, 2000, 3000]);
+        let mut results = Vec::new();
+
+        scan_with_sink(&Target::new("127.0.0.1"), &ports, &config, &Slow, &mut results).unwrap();
+        drop(config);
+
+        // The held-back timeout counts once, after its verify pass
+        let seen: Vec<(usize, usize)> = rx.iter().map(|p| (p.completed, p.total)).collect();
+        assert_eq!(seen, vec![(1, 3), (2, 3), (3, 3)]);
+        assert_eq!(results.len(), 1);
+    }
+
+    /// Cancels the scan on its tenth connect
+    struct CancelAtTen(Attempts, CancelFlag);
+
+    impl Connector for CancelAtTen {
+        fn connect(&self, addr: SocketAddr, timeout: Duration) -> TcpResult {
+            if self.0.0.load(Ordering::Relaxed) == 9 {
+                self.1.cancel();
+            }
+            self.0.connect(addr, timeout)
+        }
+    }
+
+    #[test]
+    fn cancelled_scan_keeps_partial_results() {
+        // One thread takes all 100 ports, one after another
+        let config = ScanConfig {
+            workers: Some(100),
+            connect_only: true,
+            ..Default::default()
+        };
+        let (target, ports) = (Target::new("127.0.0.1"), Ports::range(3000, 3099));
+        let connector = CancelAtTen(Attempts::default(), config.cancel.clone());
+        let mut results = Vec::new();
+
+        let outcome = scan_with_sink(&target, &ports, &config, &connector, &mut results);
+
+        // The connect in flight finishes; no port after it starts
+        assert_eq!(outcome, Err(ScanError::Cancelled));
+        assert_eq!(results.len(), 10);
+    }
+
+    #[test]
+    fn cancel_releases_a_paused_scan() {
+        let (target, ports) = (Target::new("127.0.0.1"), Ports::range(3000, 3099));
+        let config = ScanConfig::default();
+        config.pause.pause();
+
+        let connector = Attempts::default();
+        let outcome = thread::scope(|s| {
+            let scan = s.spawn(|| scan_with_sink(&target, &ports, &config, &connector, &mut Vec::new()));
+            thread::sleep(PAUSE_POLL * 2);
+            config.cancel.cancel();
+            scan.join().unwrap()
+        });
+
+        assert_eq!(outcome, Err(ScanError::Cancelled));
+        assert_eq!(connector.0.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,146 @@
+// =======================
+// SERVICE DB
+// =======================
+/// Well-known TCP services (IANA / nmap names, upper-cased). A name
+/// listed for several ports resolves to its first one in [`port_for`]
+/// (FTP → 21, not 20).
+const TCP: &[(u16, &str)] = &[
+    (21, "FTP"),
+    (20, "FTP"),
+    (22, "SSH"),
+    (23, "TELNET"),
+    (25, "SMTP"),
+    (43, "WHOIS"),
+    (53, "DNS"),
+    (79, "FINGER"),
+    (80, "HTTP"),
+    (88, "KERBEROS"),
+    (110, "POP3"),
+    (111, "RPCBIND"),
+    (113, "IDENT"),
+    (119, "NNTP"),
+    (135, "MSRPC"),
+    (139, "NETBIOS-SSN"),
+    (143, "IMAP"),
+    (161, "SNMP"),
+    (179, "BGP"),
+    (389, "LDAP"),
+    (443, "HTTPS"),
+    (445, "SMB"),
+    (465, "SMTPS"),
+    (513, "RLOGIN"),
+    (514, "RSH"),
+    (515, "PRINTER"),
+    (548, "AFP"),
+    (554, "RTSP"),
+    (587, "SUBMISSION"),
+    (631, "IPP"),
+    (636, "LDAPS"),
+    (873, "RSYNC"),
+    (990, "FTPS"),
+    (993, "IMAPS"),
+    (995, "POP3S"),
+    (1080, "SOCKS"),
+    (1433, "MSSQL"),
+    (1521, "ORACLE"),
+    (1723, "PPTP"),
+    (1883, "MQTT"),
+    (2049, "NFS"),
+    (2375, "DOCKER"),
+    (3128, "SQUID"),
+    (3306, "MYSQL"),
+    (3389, "RDP"),
+    (5060, "SIP"),
+    (5432, "POSTGRES"),
+    (5672, "AMQP"),
+    (5900, "VNC"),
+    (5985, "WINRM"),
+    (6379, "REDIS"),
+    (6667, "IRC"),
+    (8080, "HTTP-ALT"),
+    (8443, "HTTPS-ALT"),
+    (9092, "KAFKA"),
+    (9100, "JETDIRECT"),
+    (9200, "ELASTICSEARCH"),
+    (11211, "MEMCACHED"),
+    (27017, "MONGODB"),
+];
+
+/// Well-known UDP services, used instead of [`TCP`] for UDP results
+const UDP: &[(u16, &str)] = &[
+    (53, "DNS"),
+    (67, "DHCP"),
+    (68, "DHCP"),
+    (69, "TFTP"),
+    (123, "NTP"),
+    (137, "NETBIOS-NS"),
+    (161, "SNMP"),
+    (162, "SNMP"),
+    (500, "IKE"),
+    (514, "SYSLOG"),
+    (1900, "SSDP"),
+    (5353, "MDNS"),
+];
+
+/// Name of the TCP service usually on `port`; `system` for other ports
+/// below 20, `unknown` for the rest
+pub fn service_for(port: u16) -> &'static str {
+    match lookup(TCP, port) {
+        Some(name) => name,
+        None if (1..=19).contains(&port) => "system",
+        None => "unknown",
+    }
+}
+
+/// [`service_for`] for UDP ports
+pub fn udp_service_for(port: u16) -> &'static str {
+    lookup(UDP, port).unwrap_or("unknown")
+}
+
+/// Inverse of [`service_for`]: `"ssh"` → 22 (case-insensitive)
+pub fn port_for(name: &str) -> Option<u16> {
+    TCP.iter()
+        .find(|(_, n)| n.eq_ignore_ascii_case(name))
+        .map(|&(port, _)| port)
+}
+
+fn lookup(table: &[(u16, &'static str)], port: u16) -> Option<&'static str> {
+    table.iter().find(|&&(p, _)| p == port).map(|&(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn well_known_ports_have_names() {
+        let expected = [
+            (20, "FTP"), (21, "FTP"), (22, "SSH"), (23, "TELNET"), (25, "SMTP"),
+            (53, "DNS"), (80, "HTTP"), (88, "KERBEROS"), (110, "POP3"), (111, "RPCBIND"),
+            (135, "MSRPC"), (139, "NETBIOS-SSN"), (143, "IMAP"), (161, "SNMP"), (389, "LDAP"),
+            (443, "HTTPS"), (445, "SMB"), (465, "SMTPS"), (587, "SUBMISSION"), (636, "LDAPS"),
+            (993, "IMAPS"), (995, "POP3S"), (1433, "MSSQL"), (1521, "ORACLE"), (2049, "NFS"),
+            (3306, "MYSQL"), (3389, "RDP"), (5432, "POSTGRES"), (5900, "VNC"), (6379, "REDIS"),
+            (8080, "HTTP-ALT"), (9200, "ELASTICSEARCH"), (11211, "MEMCACHED"), (27017, "MONGODB"),
+        ];
+        for (port, name) in expected {
+            assert_eq!(service_for(port), name, "port {}", port);
+        }
+    }
+
+    #[test]
+    fn fallbacks_and_udp() {
+        assert_eq!(service_for(7), "system");
+        assert_eq!(service_for(40000), "unknown");
+        assert_eq!(udp_service_for(123), "NTP");
+        assert_eq!(udp_service_for(22), "unknown");
+    }
+
+    #[test]
+    fn names_map_back_to_their_first_port() {
+        assert_eq!(port_for("ftp"), Some(21));
+        assert_eq!(port_for("MongoDB"), Some(27017));
+        assert_eq!(port_for("system"), None);
+        assert_eq!(port_for("gopher"), None);
+    }
+}
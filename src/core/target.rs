@@ -1,12 +1,417 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+/// Default limit on how many hosts one expression may expand to
+pub const DEFAULT_HOST_CAP: usize = 4096;
+
 #[derive(Clone, Debug)]
 pub struct Target {
+    /// What gets resolved and reported: the input, punycode-encoded
+    /// if it was an internationalized name (`xn--bcher-kva.de`)
     pub host: String,
+    /// The name as given (`bücher.de`), for display; without the
+    /// scheme, path and port of a pasted URL
+    pub name: String,
+    /// Port embedded in the input (`example.com:8080`, `[::1]:22`)
+    pub port: Option<u16>,
+}
+
+/// Why a target expression could not be expanded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetError {
+    /// `addr/prefix` where addr is not an IP or prefix is too long
+    InvalidCidr(String),
+    /// Expansion would exceed the host cap
+    TooManyHosts {
+        cidr: String,
+        host_bits: u32,
+        cap: usize,
+    },
+    /// Two hosts of one expression name different ports (or one a port
+    /// and the other none)
+    MixedPorts { first: String, other: String },
 }
 
+impl fmt::Display for TargetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidCidr(c) => write!(f, "invalid CIDR '{}'", c),
+            Self::TooManyHosts { cidr, host_bits, cap } => {
+                if *host_bits < 64 {
+                    write!(f, "{} expands to {} hosts (cap {}, see --max-hosts)", cidr, 1u64 << host_bits, cap)
+                } else {
+                    write!(f, "{} expands to 2^{} hosts (cap {}, see --max-hosts)", cidr, host_bits, cap)
+                }
+            }
+            Self::MixedPorts { first, other } => write!(
+                f,
+                "'{}' and '{}' name different ports; one port spec covers every host, so scan them separately",
+                first, other
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TargetError {}
+
 impl Target {
+    /// A host, IP or pasted URL: `https://example.com:8443/path` gives
+    /// host `example.com` and port 8443
     pub fn new(input: &str) -> Self {
+        let (input, port) = split_url(input);
+        // `[::1]` → `::1`; the brackets stay in `name`
+        if let Some(inner) = input.strip_prefix('[').and_then(|r| r.strip_suffix(']'))
+            && inner.parse::<Ipv6Addr>().is_ok()
+        {
+            return Self {
+                host: inner.to_string(),
+                name: input.to_string(),
+                port,
+            };
+        }
+        // A name idna rejects is kept as is and simply fails to resolve
+        let host = match input.is_ascii() {
+            true => input.to_string(),
+            false => idna::domain_to_ascii(input).unwrap_or_else(|_| input.to_string()),
+        };
         Self {
-            host: input.to_string(),
+            host,
+            name: input.to_string(),
+            port,
+        }
+    }
+
+    /// Port the hosts of a target expression name (`a.com:8080,
+    /// b.com:8080` → `Some(8080)`). One port spec covers every host of a
+    /// scan, so hosts naming different ports, or some a port and some
+    /// none, are [`TargetError::MixedPorts`] rather than each scanning
+    /// the other's.
+    pub fn embedded_port(input: &str) -> Result<Option<u16>, TargetError> {
+        let port_of = |item: &str| match is_cidr(item) {
+            true => None,
+            false => Target::new(item).port,
+        };
+        let mut items = input.split(',').map(str::trim).filter(|h| !h.is_empty());
+        let Some(first) = items.next() else {
+            return Ok(None);
+        };
+        let port = port_of(first);
+        match items.find(|item| port_of(item) != port) {
+            Some(other) => Err(TargetError::MixedPorts {
+                first: first.to_string(),
+                other: other.to_string(),
+            }),
+            None => Ok(port),
+        }
+    }
+
+    /// Same as [`Target::expand_capped`] with [`DEFAULT_HOST_CAP`]
+    pub fn expand(input: &str) -> Result<Vec<Target>, TargetError> {
+        Self::expand_capped(input, DEFAULT_HOST_CAP)
+    }
+
+    /// Comma-separated hosts and IPv4/IPv6 CIDR blocks → one target
+    /// per host. Blank entries and duplicates are dropped; order is kept.
+    ///
+    /// Every address of a block is included (network and broadcast too).
+    /// A block larger than `cap` hosts is rejected rather than truncated.
+    pub fn expand_capped(input: &str, cap: usize) -> Result<Vec<Target>, TargetError> {
+        let mut targets: Vec<Target> = Vec::new();
+
+        for item in input.split(',').map(str::trim).filter(|h| !h.is_empty()) {
+            let hosts = match item.split_once('/') {
+                Some((addr, prefix)) if is_cidr(item) => expand_cidr(item, addr, prefix, cap)?,
+                _ => vec![item.to_string()],
+            };
+
+            for target in hosts.iter().map(|h| Target::new(h)) {
+                if !targets.iter().any(|t| t.host == target.host) {
+                    targets.push(target);
+                }
+            }
+        }
+
+        Ok(targets)
+    }
+
+    /// Drop targets matched by `spec`: comma-separated hosts/IPs or CIDR
+    /// blocks. Entries that match nothing are a no-op.
+    pub fn exclude(targets: Vec<Target>, spec: &str) -> Result<Vec<Target>, TargetError> {
+        let excluded = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(Exclusion::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(targets
+            .into_iter()
+            .filter(|t| !excluded.iter().any(|e| e.matches(&t.host)))
+            .collect())
+    }
+}
+
+// =======================
+// URL INPUT
+// =======================
+/// `ip/prefix`, as opposed to a host or URL with a path; a prefix out
+/// of range is still CIDR (and an error), `example.com/24` is a path
+fn is_cidr(item: &str) -> bool {
+    item.split_once('/').is_some_and(|(addr, prefix)| {
+        addr.trim().parse::<IpAddr>().is_ok()
+            && !prefix.trim().is_empty()
+            && prefix.trim().bytes().all(|b| b.is_ascii_digit())
+    })
+}
+
+/// Host part and port of `scheme://user@host:port/path?query`; every
+/// piece but the host is optional. A bare IPv6 address has no port
+/// (`::1` stays whole); `[::1]:22` does. An unparsable port leaves the
+/// input as it was, to fail at resolution.
+fn split_url(input: &str) -> (&str, Option<u16>) {
+    let rest = match input.split_once("://") {
+        Some((scheme, rest))
+            if !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) =>
+        {
+            rest
         }
+        _ => input,
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(bracketed) => match bracketed.split_once("]:") {
+            Some((v6, port)) => (&authority[..v6.len() + 2], port),
+            None => return (authority, None),
+        },
+        None => match authority.split_once(':') {
+            Some((host, port)) if !port.contains(':') => (host, port),
+            _ => return (authority, None),
+        },
+    };
+    match port.parse::<u16>() {
+        Ok(port) if port > 0 && !host.is_empty() => (host, Some(port)),
+        _ => (authority, None),
+    }
+}
+
+// =======================
+// EXCLUSIONS
+// =======================
+enum Exclusion {
+    Host(String),
+    Ip(IpAddr),
+    Block(IpAddr, u32),
+}
+
+impl Exclusion {
+    fn parse(item: &str) -> Result<Self, TargetError> {
+        let Some((addr, prefix)) = item.split_once('/') else {
+            return Ok(match item.parse() {
+                Ok(ip) => Self::Ip(ip),
+                Err(_) => Self::Host(Target::new(item).host),
+            });
+        };
+
+        let invalid = || TargetError::InvalidCidr(item.to_string());
+        let addr: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+        let prefix: u32 = prefix.trim().parse().map_err(|_| invalid())?;
+        if prefix > bits(addr).1 {
+            return Err(invalid());
+        }
+        Ok(Self::Block(addr, prefix))
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        let ip = host.parse::<IpAddr>();
+        match (self, ip) {
+            (Self::Host(h), _) => h == host,
+            (Self::Ip(e), Ok(ip)) => *e == ip,
+            (Self::Block(net, prefix), Ok(ip)) => {
+                let ((a, width), (b, other)) = (bits(*net), bits(ip));
+                width == other && (a ^ b).checked_shr(width - prefix).unwrap_or(0) == 0
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Address as an integer plus its width in bits
+fn bits(ip: IpAddr) -> (u128, u32) {
+    match ip {
+        IpAddr::V4(v4) => (u32::from(v4) as u128, 32),
+        IpAddr::V6(v6) => (u128::from(v6), 128),
+    }
+}
+
+// =======================
+// CIDR
+// =======================
+fn expand_cidr(cidr: &str, addr: &str, prefix: &str, cap: usize) -> Result<Vec<String>, TargetError> {
+    let invalid = || TargetError::InvalidCidr(cidr.to_string());
+
+    let addr: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+    let prefix: u32 = prefix.trim().parse().map_err(|_| invalid())?;
+    let width = if addr.is_ipv4() { 32 } else { 128 };
+    if prefix > width {
+        return Err(invalid());
+    }
+
+    let host_bits = width - prefix;
+    let too_many = TargetError::TooManyHosts {
+        cidr: cidr.to_string(),
+        host_bits,
+        cap,
+    };
+    if host_bits >= 64 || (1u64 << host_bits) > cap as u64 {
+        return Err(too_many);
+    }
+    let count = 1u128 << host_bits;
+
+    Ok(match addr {
+        IpAddr::V4(ip) => {
+            let base = u32::from(ip) as u128 & !(count - 1);
+            (0..count)
+                .map(|i| Ipv4Addr::from((base + i) as u32).to_string())
+                .collect()
+        }
+        IpAddr::V6(ip) => {
+            let base = u128::from(ip) & !(count - 1);
+            (0..count).map(|i| Ipv6Addr::from(base + i).to_string()).collect()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hosts(input: &str) -> Vec<String> {
+        Target::expand(input).unwrap().into_iter().map(|t| t.host).collect()
+    }
+
+    #[test]
+    fn urls_and_ports_are_stripped_from_hosts() {
+        let parts = |input| {
+            let t = Target::new(input);
+            (t.host, t.port)
+        };
+
+        assert_eq!(parts("http://x.com"), ("x.com".into(), None));
+        assert_eq!(parts("https://user@x.com:8443/path?q=1#top"), ("x.com".into(), Some(8443)));
+        assert_eq!(parts("x.com:8080"), ("x.com".into(), Some(8080)));
+        assert_eq!(parts("[::1]:22"), ("::1".into(), Some(22)));
+        assert_eq!(parts("::1"), ("::1".into(), None));
+        assert_eq!(parts("192.0.2.7"), ("192.0.2.7".into(), None));
+        assert_eq!(parts("x.com:http"), ("x.com:http".into(), None));
+        assert_eq!(Target::new("http://[::1]:22/").name, "[::1]");
+    }
+
+    #[test]
+    fn url_targets_expand_and_report_their_ports() {
+        assert_eq!(hosts("https://a.com/x, 10.0.0.5/31, b.com:22"), vec!["a.com", "10.0.0.4", "10.0.0.5", "b.com"]);
+        // Without a scheme a path is still a path, not a prefix
+        assert_eq!(hosts("example.com/path, x.com:8080/admin, example.com/24"), vec!["example.com", "x.com"]);
+        assert_eq!(Target::embedded_port("x.com:8080/admin, y.com:8080"), Ok(Some(8080)));
+        assert_eq!(Target::embedded_port("a.com:8080, https://c.com:8080/x"), Ok(Some(8080)));
+        assert_eq!(Target::embedded_port("a.com, 10.0.0.0/30"), Ok(None));
+        assert_eq!(
+            Target::embedded_port("a.com:8080, [::1]:22"),
+            Err(TargetError::MixedPorts { first: "a.com:8080".into(), other: "[::1]:22".into() })
+        );
+        assert!(Target::embedded_port("a.com:8080, b.com").is_err());
+    }
+
+    #[test]
+    fn host_list_keeps_order_and_drops_duplicates() {
+        assert_eq!(hosts("b.com, a.com,,b.com"), vec!["b.com", "a.com"]);
+    }
+
+    #[test]
+    fn ipv4_cidr() {
+        assert_eq!(
+            hosts("10.0.0.5/30"),
+            vec!["10.0.0.4", "10.0.0.5", "10.0.0.6", "10.0.0.7"]
+        );
+        assert_eq!(hosts("192.168.1.0/24").len(), 256);
+        assert_eq!(hosts("10.0.0.1/32"), vec!["10.0.0.1"]);
+    }
+
+    #[test]
+    fn ipv6_cidr_124() {
+        let got = hosts("2001:db8::/124");
+        assert_eq!(got.len(), 16);
+        assert_eq!(got[0], "2001:db8::");
+        assert_eq!(got[15], "2001:db8::f");
+    }
+
+    #[test]
+    fn oversized_blocks_are_rejected() {
+        assert!(matches!(
+            Target::expand("2001:db8::/64"),
+            Err(TargetError::TooManyHosts { host_bits: 64, .. })
+        ));
+        assert!(matches!(
+            Target::expand("10.0.0.0/8"),
+            Err(TargetError::TooManyHosts { host_bits: 24, .. })
+        ));
+        assert!(Target::expand_capped("10.0.0.0/28", 8).is_err());
+        assert_eq!(Target::expand_capped("10.0.0.0/29", 8).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn malformed_cidr() {
+        assert_eq!(
+            Target::expand("10.0.0.0/33").err(),
+            Some(TargetError::InvalidCidr("10.0.0.0/33".into()))
+        );
+        assert_eq!(
+            Target::expand("2001:db8::/129").err(),
+            Some(TargetError::InvalidCidr("2001:db8::/129".into()))
+        );
+    }
+
+    fn excluded(input: &str, spec: &str) -> Vec<String> {
+        let targets = Target::expand(input).unwrap();
+        Target::exclude(targets, spec).unwrap().into_iter().map(|t| t.host).collect()
+    }
+
+    #[test]
+    fn exclude_hosts_and_blocks() {
+        assert_eq!(
+            excluded("10.0.0.0/29", "10.0.0.1, 10.0.0.4/31"),
+            vec!["10.0.0.0", "10.0.0.2", "10.0.0.3", "10.0.0.6", "10.0.0.7"]
+        );
+        assert_eq!(excluded("a.com,b.com", "b.com"), vec!["a.com"]);
+        assert_eq!(excluded("2001:db8::/126", "2001:db8:0::2"), vec!["2001:db8::", "2001:db8::1", "2001:db8::3"]);
+    }
+
+    #[test]
+    fn exclude_outside_range_is_noop() {
+        assert_eq!(excluded("10.0.0.0/30", "192.168.0.0/16,10.0.1.1").len(), 4);
+        assert!(Target::exclude(Vec::new(), "10.0.0.0/33").is_err());
+    }
+
+    #[test]
+    fn encodes_idn_hosts() {
+        let t = Target::new("bücher.de");
+        assert_eq!(t.host, "xn--bcher-kva.de");
+        assert_eq!(t.name, "bücher.de");
+
+        assert_eq!(Target::new("Example.com").host, "Example.com");
+        assert_eq!(hosts("bücher.de,xn--bcher-kva.de"), vec!["xn--bcher-kva.de"]);
+        assert_eq!(excluded("bücher.de,a.com", "bücher.de"), vec!["a.com"]);
+    }
+
+    #[test]
+    fn bracketed_ipv6_literal_is_unwrapped() {
+        let t = Target::new("[::1]");
+        assert_eq!((t.host.as_str(), t.name.as_str()), ("::1", "[::1]"));
+        assert_eq!(hosts("[2001:db8::1],::1"), vec!["2001:db8::1", "::1"]);
+        // Only real IPv6 literals lose their brackets
+        assert_eq!(Target::new("[example]").host, "[example]");
     }
 }
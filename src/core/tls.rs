@@ -0,0 +1,223 @@
+/// What a completed TLS handshake showed (`tls` feature)
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TlsInfo {
+    /// Negotiated version: `TLSv1.3`, `TLSv1.2`
+    pub protocol: String,
+    /// ALPN protocol the server picked (`h2`, `http/1.1`)
+    pub alpn: Option<String>,
+    /// Subject common name of the leaf certificate
+    pub common_name: Option<String>,
+    /// DNS names and IP addresses of the subjectAltName extension
+    pub san: Vec<String>,
+    /// Expiry of the leaf certificate, `2027-03-01 12:00:00 UTC`
+    pub not_after: Option<String>,
+}
+
+#[cfg(feature = "tls")]
+pub(crate) use handshake::handshake;
+
+#[cfg(feature = "tls")]
+mod handshake {
+    use std::{
+        io::{Read, Write},
+        net::IpAddr,
+        sync::{Arc, OnceLock},
+    };
+
+    use rustls::{
+        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+        pki_types::{CertificateDer, ServerName, UnixTime},
+        ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme,
+    };
+    use x509_parser::{extensions::GeneralName, prelude::*};
+
+    use super::TlsInfo;
+
+    /// Accepts any certificate: the scan reports what the server shows,
+    /// it does not decide whether to trust it. Handshake signatures are
+    /// still checked, so the certificate is the one the server holds.
+    #[derive(Debug)]
+    struct AnyCertificate(Arc<CryptoProvider>);
+
+    impl ServerCertVerifier for AnyCertificate {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+
+    /// Built once; every probe shares it
+    fn config() -> Option<Arc<ClientConfig>> {
+        static CONFIG: OnceLock<Option<Arc<ClientConfig>>> = OnceLock::new();
+        CONFIG
+            .get_or_init(|| {
+                let provider = Arc::new(ring::default_provider());
+                let mut config = ClientConfig::builder_with_provider(provider.clone())
+                    .with_safe_default_protocol_versions()
+                    .ok()?
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(AnyCertificate(provider)))
+                    .with_no_client_auth();
+                config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+                Some(Arc::new(config))
+            })
+            .clone()
+    }
+
+    /// Full handshake over `stream` (timeouts already set), sending
+    /// `sni` when the host is a name. `None` if it fails, e.g. against
+    /// a server that only speaks TLS 1.0/1.1.
+    pub(crate) fn handshake<S: Read + Write>(stream: &mut S, sni: Option<&str>, ip: IpAddr) -> Option<TlsInfo> {
+        let name = match sni {
+            Some(host) => ServerName::try_from(host.to_string()).ok()?,
+            None => ServerName::IpAddress(ip.into()),
+        };
+        let mut conn = ClientConnection::new(config()?, name).ok()?;
+        while conn.is_handshaking() {
+            conn.complete_io(stream).ok()?;
+        }
+
+        let protocol = version_name(&format!("{:?}", conn.protocol_version()?));
+        let alpn = conn.alpn_protocol().map(|p| String::from_utf8_lossy(p).into_owned());
+        let mut info = TlsInfo {
+            protocol,
+            alpn,
+            common_name: None,
+            san: Vec::new(),
+            not_after: None,
+        };
+        let leaf = conn.peer_certificates().and_then(|certs| certs.first());
+        if let Some(Ok((_, cert))) = leaf.map(|der| X509Certificate::from_der(der)) {
+            info.common_name = cert
+                .subject()
+                .iter_common_name()
+                .next()
+                .and_then(|cn| cn.as_str().ok())
+                .map(str::to_string);
+            if let Ok(Some(san)) = cert.subject_alternative_name() {
+                info.san = san
+                    .value
+                    .general_names
+                    .iter()
+                    .filter_map(|name| match name {
+                        GeneralName::DNSName(dns) => Some(dns.to_string()),
+                        GeneralName::IPAddress(bytes) => san_ip(bytes),
+                        _ => None,
+                    })
+                    .collect();
+            }
+            info.not_after = expiry_text(cert.validity().not_after.timestamp());
+        }
+        Some(info)
+    }
+
+    /// `TLSv1_3` → `TLSv1.3`
+    fn version_name(version: &str) -> String {
+        version.replace('_', ".")
+    }
+
+    /// Unix time as `2027-03-01 12:00:00 UTC`
+    fn expiry_text(timestamp: i64) -> Option<String> {
+        chrono::DateTime::from_timestamp(timestamp, 0).map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+    }
+
+    /// SAN entry of a raw IP address (4 or 16 bytes)
+    fn san_ip(bytes: &[u8]) -> Option<String> {
+        let ip: IpAddr = match bytes.len() {
+            4 => <[u8; 4]>::try_from(bytes).ok()?.into(),
+            16 => <[u8; 16]>::try_from(bytes).ok()?.into(),
+            _ => return None,
+        };
+        Some(ip.to_string())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn formats_versions_expiry_and_san_addresses() {
+            assert_eq!(version_name("TLSv1_3"), "TLSv1.3");
+            assert_eq!(expiry_text(1_800_000_000).as_deref(), Some("2027-01-15 08:00:00 UTC"));
+            assert_eq!(san_ip(&[10, 0, 0, 1]).as_deref(), Some("10.0.0.1"));
+            let mut v6 = [0u8; 16];
+            v6[15] = 1;
+            assert_eq!(san_ip(&v6).as_deref(), Some("::1"));
+            assert_eq!(san_ip(&[1, 2, 3]), None);
+        }
+
+        #[test]
+        fn reads_the_certificate_of_a_local_server() {
+            use rustls::{
+                pki_types::{pem::PemObject, PrivateKeyDer},
+                ServerConfig, ServerConnection,
+            };
+            use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+            let certs = CertificateDer::pem_slice_iter(include_bytes!("../../tests/data/tls-cert.pem"))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let key = PrivateKeyDer::from_pem_slice(include_bytes!("../../tests/data/tls-key.pem")).unwrap();
+            let mut server = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .unwrap();
+            server.alpn_protocols = vec![b"h2".to_vec()];
+            let server = Arc::new(server);
+
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let addr = listener.local_addr().unwrap();
+            let accept = std::thread::spawn(move || {
+                let (mut s, _) = listener.accept().unwrap();
+                let mut conn = ServerConnection::new(server).unwrap();
+                while conn.is_handshaking() {
+                    conn.complete_io(&mut s).unwrap();
+                }
+                conn.send_close_notify();
+                let _ = conn.complete_io(&mut s);
+            });
+
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let info = handshake(&mut stream, Some("localhost"), addr.ip()).unwrap();
+            accept.join().unwrap();
+
+            assert_eq!(info.protocol, "TLSv1.3");
+            assert_eq!(info.alpn.as_deref(), Some("h2"));
+            assert_eq!(info.common_name.as_deref(), Some("wise1738 test"));
+            assert_eq!(info.san, vec!["localhost", "127.0.0.1"]);
+            assert!(info.not_after.unwrap().starts_with("2126-"));
+        }
+    }
+}
@@ -1,6 +1,46 @@
-mod tui;
-mod core;
+use std::process::ExitCode;
 
-fn main() -> std::io::Result<()> {
-    tui::terminal::run()
+use wise1738::{cli, tui};
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    // --no-color applies to both modes, wherever it appears
+    let no_color = args.iter().any(|a| a == "--no-color");
+    args.retain(|a| a != "--no-color");
+    let color = cli::color_enabled(no_color);
+    // TUI in the normal screen, so the session stays in the scrollback
+    let inline = args.iter().any(|a| a == "--inline");
+    args.retain(|a| a != "--inline");
+
+    // Headless: wise1738 --cli <host> [ports] [flags]
+    if args.first().is_some_and(|a| a == "--cli") {
+        return cli::run(&args[1..]);
+    }
+    // cat hosts.txt | wise1738 --stdin [ports] [flags]
+    if args.first().is_some_and(|a| a == "--stdin") {
+        return cli::run_stdin(&args[1..]);
+    }
+
+    // wise1738 --bench [ports] [--listeners n] [--rounds n] [flags]
+    if args.first().is_some_and(|a| a == "--bench") {
+        return cli::run_bench(&args[1..]);
+    }
+
+    // Before the terminal is taken over, or it would be a TUI command
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", cli::help());
+        return ExitCode::SUCCESS;
+    }
+
+    // wise1738 scan 10.0.0.1 80 → TUI starts with that command already run
+    let initial_command = (!args.is_empty()).then(|| args.join(" "));
+
+    match tui::terminal::run(initial_command, color, inline) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
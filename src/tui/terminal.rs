@@ -1,27 +1,47 @@
-use std::{fs, io, time::Duration};
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs, io,
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
 
-use chrono::Local;
+use chrono::{
+    format::{Item, StrftimeItems},
+    Local,
+};
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
-    Terminal,
+    widgets::{Block, Borders, Clear, Gauge, Paragraph, Row, Table, TableState, Wrap},
+    Terminal, TerminalOptions, Viewport,
 };
 
 use printpdf::*;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-use crate::core::{
-    engine,
-    ports::Ports,
-    scanner::{PortStatus, ScanResult},
+use crate::{
+    cli::{self, ScanArgs, PORTS_ENV},
+    core::{
+        engine::{self, ScanMeta, ScanReport},
+        ports::{self, PortParseError, Ports},
+        report,
+        scanner::{
+            self, AddressFamily, CancelFlag, PauseFlag, PortStatus, ResultSink, ScanConfig, ScanMetrics, ScanProgress,
+            ScanResult,
+        },
+        target::Target,
+    },
 };
 
 // =======================
@@ -29,20 +49,191 @@ use crate::core::{
 // =======================
 enum UiState {
     Idle,
+    Scanning,
     ExitPending,
+    /// Details popup for the selected row (Esc closes)
+    Detail,
+    /// Between two runs of `watch` (Esc stops)
+    Watching,
+}
+
+/// Which sections the output pane renders
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum View {
+    All,
+    Open,
+    /// One line per host: `10.0.0.5: 22,80,443 open`
+    Summary,
+}
+
+/// Pane that Ctrl+Up/Down and PgUp/PgDn scroll (Tab switches)
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Output,
+    Events,
 }
 
+/// EVENTS line severity, shown as its color
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+/// One line of the EVENTS pane
+struct Event {
+    level: Level,
+    msg: String,
+    /// Local time, in the format set when the event was logged
+    ts: String,
+}
+
+/// Default number of events kept for scrollback (`set events <n>`)
+const EVENT_HISTORY: usize = 1000;
+
+/// Commands kept for Up/Down recall; the oldest drop off first
+const COMMAND_HISTORY: usize = 200;
+
+/// Rows the layout needs: status 1 + command 3 + output 10 + events 6
+const MIN_ROWS: u16 = 20;
+
+/// Default event timestamp format (`set timefmt <fmt>`)
+const TIME_FORMAT: &str = "%H:%M:%S";
+
+/// Scan messages handled per event-loop tick; the rest wait for the next
+/// one, keeping a frame cheap while a large scan streams in
+const ROWS_PER_TICK: usize = 2048;
+
+/// Columns of the status bar's progress gauge
+const GAUGE_WIDTH: u16 = 30;
+
+/// `help` text, shown in the output pane (scrollable like results)
+const HELP: &[&str] = &[
+    "COMMANDS",
+    "  scan <host> [ports] [flags]   Scan a host, list or CIDR: scan example.com 22,80,443",
+    "                                scan 10.0.0.0/24 web --fast · scan ::1 1-1024 udp",
+    "  watch <host> [ports] [--interval 30s]   Rescan on an interval, report changes (Esc stops)",
+    "  rescan-open                   Scan the last scan's open ports again",
+    "  cancel                        Stop the running scan (or press Esc); results so far stay",
+    "  ports <expr>                  Preview a port expression: ports web,8000-8010,!8005",
+    "  view open|all|summary         Open ports only, every port, or one line per host",
+    "  filter <text> · filter clear  Only rows whose port, state or service has the text",
+    "  export json|csv|xml [path]    Write the last results (json pretty [path] indents)",
+    "  export pdf                    PDF report of the last results",
+    "  save <path> · load <path>     Keep the last results in a file / bring them back",
+    "  banners on|off                Keep the first bytes each open port sends",
+    "  set                           Show the settings below",
+    "  set timeout <ms|dur>          Connect timeout: set timeout 2s",
+    "  set retries <n>               Retries after a timeout",
+    "  set workers <n>               Ports per thread / connects in flight",
+    "  set rate <n>|off              Most ports started per second",
+    "  set family any|v4|v6          Address family to scan",
+    "  set events <n> · set scroll keep|reset · set timefmt <fmt>|iso",
+    "  help                          This text",
+    "  exit                          Quit (also q)",
+    "",
+    "PORTS",
+    "  22,80,443 · 1-1024 · 8000:8100 · web, mail, db, remote, file · ssh, https",
+    "  top100, top1000, all · !8005 leaves a port out · udp after the host scans UDP",
+    "",
+    "KEYS",
+    "  ↑ ↓ command history · Ctrl+↑ ↓ / PgUp PgDn scroll · Tab output/events",
+    "  Enter on an empty prompt: details of the selected port · Alt+O / Alt+C: open / closed",
+    "  Alt+P pause/resume a scan · Esc cancel a scan or stop a watch",
+];
+
+/// Rows PgUp / PgDn move the focused pane by
+const PAGE_ROWS: usize = 10;
+
+/// Spinner frames, advanced once per event-loop tick (~100ms)
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
 // =======================
 // APP STATE
 // =======================
 struct App {
     state: UiState,
     command: String,
-    events: Vec<String>,
-    open: Vec<String>,
-    closed: Vec<String>,
-    scroll: usize,
+    /// Byte offset of the cursor in `command`, on a grapheme boundary
+    cursor: usize,
+    /// Submitted commands, oldest first; Up/Down recall them
+    history: Vec<String>,
+    /// Entry of `history` in the command box; `None` while typing anew
+    history_index: Option<usize>,
+    /// What was typed before Up started recalling, restored past the
+    /// newest entry
+    draft: String,
+    events: VecDeque<Event>,
+    /// Lines scrolled back from the newest event
+    events_scroll: usize,
+    /// Oldest events are dropped beyond this many
+    event_cap: usize,
+    /// strftime format of event timestamps
+    time_format: String,
+    focus: Focus,
+    /// Selected row of the results table (first line shown in summary view)
+    selected: usize,
     last_results: Vec<ScanResult>,
+    /// Meta of the scan `last_results` came from; `None` while one runs
+    last_meta: Option<ScanMeta>,
+    /// Last command failed; COMMAND box turns red until the next key
+    last_error: bool,
+    spinner: usize,
+    scan_rx: Option<Receiver<ScanMsg>>,
+    /// Current scan covers more than one host (adds a HOST column)
+    multi_host: bool,
+    default_ports: Ports,
+    /// Last port spec typed and its parsed set, so rescanning the same
+    /// spec skips the parse (and its sort)
+    port_cache: Option<(String, Ports)>,
+    view: View,
+    /// Output pane shows [`HELP`] until the next scan or `view`
+    help: bool,
+    /// `filter` text, lower-cased: only table rows containing it are drawn
+    filter: Option<String>,
+    /// First table row of the OPEN / CLOSED sections, as last drawn
+    sections: Cell<(Option<usize>, Option<usize>)>,
+    /// Table offset carried between frames
+    table: Cell<TableState>,
+    /// Largest useful `scroll` for the output, as last drawn
+    max_scroll: Cell<usize>,
+    /// Lines the EVENTS pane holds, as last drawn
+    events_rows: Cell<usize>,
+    /// Keep the output scroll position across rescans (`set scroll keep`)
+    keep_scroll: bool,
+    /// Off with `NO_COLOR` / `--no-color`
+    color: bool,
+    /// Base settings for new scans, changed with `set timeout|retries|workers|family`
+    config: ScanConfig,
+    /// Ports of the running scan still missing a result, with how many
+    /// hosts each is pending on
+    pending: BTreeMap<u16, usize>,
+    /// Progress of the running scan, for the status bar gauge
+    gauge: Option<ScanGauge>,
+    /// Hosts and settings of the last scan, reused by `rescan-open`
+    last_scan: Option<(Vec<Target>, ScanConfig)>,
+    /// Running `watch`, if any
+    watch: Option<Watch>,
+    /// (host, port) whose open state changed in the last watch run
+    changed: Vec<(String, u16)>,
+    /// Shared with the running scan's workers; Alt+P toggles it
+    pause: PauseFlag,
+    /// Stop switch of the running scan (Esc or `cancel`); new per scan
+    cancel: CancelFlag,
+}
+
+/// `watch`: the scan it repeats and what the last run found open
+struct Watch {
+    interval: Duration,
+    /// When the next run starts
+    next: Instant,
+    targets: Vec<Target>,
+    ports: Ports,
+    config: ScanConfig,
+    /// Open results of the last finished run; `None` before the first
+    previous: Option<Vec<ScanResult>>,
+    runs: usize,
 }
 
 impl App {
@@ -50,19 +241,217 @@ impl App {
         Self {
             state: UiState::Idle,
             command: String::new(),
-            events: Vec::new(),
-            open: Vec::new(),
-            closed: Vec::new(),
-            scroll: 0,
+            cursor: 0,
+            history: Vec::new(),
+            history_index: None,
+            draft: String::new(),
+            events: VecDeque::new(),
+            events_scroll: 0,
+            event_cap: EVENT_HISTORY,
+            time_format: TIME_FORMAT.to_string(),
+            focus: Focus::Output,
+            selected: 0,
             last_results: Vec::new(),
+            last_meta: None,
+            last_error: false,
+            spinner: 0,
+            scan_rx: None,
+            multi_host: false,
+            default_ports: Ports::top_basic(),
+            port_cache: None,
+            view: View::All,
+            help: false,
+            filter: None,
+            sections: Cell::new((None, None)),
+            table: Cell::new(TableState::default()),
+            max_scroll: Cell::new(0),
+            events_rows: Cell::new(0),
+            keep_scroll: false,
+            color: true,
+            config: ScanConfig::default(),
+            pending: BTreeMap::new(),
+            gauge: None,
+            last_scan: None,
+            watch: None,
+            changed: Vec::new(),
+            pause: PauseFlag::default(),
+            cancel: CancelFlag::default(),
+        }
+    }
+
+    /// Display form of a result's host (`bücher.de` for `xn--bcher-kva.de`)
+    fn host_name<'a>(&'a self, host: &'a str) -> &'a str {
+        self.last_scan
+            .iter()
+            .flat_map(|(targets, _)| targets)
+            .find(|t| t.host == host)
+            .map_or(host, |t| t.name.as_str())
+    }
+
+    fn log(&mut self, level: Level, msg: impl Into<String>) {
+        self.events.push_back(Event {
+            level,
+            msg: msg.into(),
+            ts: Local::now().format(&self.time_format).to_string(),
+        });
+        while self.events.len() > self.event_cap {
+            self.events.pop_front();
+        }
+        // Keep a scrolled-back view pinned to the same lines
+        if self.events_scroll > 0 {
+            self.events_scroll = (self.events_scroll + 1).min(self.max_events_scroll());
+        }
+    }
+
+    /// Scrolling back further would leave the EVENTS pane part empty
+    fn max_events_scroll(&self) -> usize {
+        self.events.len().saturating_sub(self.events_rows.get())
+    }
+
+    /// Foreground color, or plain when colors are off
+    fn fg(&self, color: Color) -> Style {
+        if self.color {
+            Style::default().fg(color)
+        } else {
+            Style::default()
+        }
+    }
+
+    /// State once no scan runs: waiting for the next watch run, or idle
+    fn idle_state(&self) -> UiState {
+        match self.watch {
+            Some(_) => UiState::Watching,
+            None => UiState::Idle,
+        }
+    }
+
+    /// One more result for `port` arrived
+    fn port_done(&mut self, port: u16) {
+        if let Some(left) = self.pending.get_mut(&port) {
+            *left -= 1;
+            if *left == 0 {
+                self.pending.remove(&port);
+            }
         }
     }
 
     fn event(&mut self, msg: impl Into<String>) {
-        let ts = Local::now().format("%H:%M:%S");
-        self.events.push(format!("[{}] {}", ts, msg.into()));
-        if self.events.len() > 6 {
-            self.events.remove(0);
+        self.log(Level::Info, msg);
+    }
+
+    fn warn(&mut self, msg: impl Into<String>) {
+        self.log(Level::Warn, msg);
+    }
+
+    /// Event for a failed command; also flags the COMMAND box
+    fn error(&mut self, msg: impl Into<String>) {
+        self.last_error = true;
+        self.log(Level::Error, msg);
+    }
+}
+
+// =======================
+// COMMAND LINE EDITING
+// =======================
+/// Cursor moves and deletes go by grapheme, so `é` typed as e + accent
+/// is one step
+impl App {
+    fn insert(&mut self, c: char) {
+        self.command.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /// Start of the grapheme before the cursor
+    fn prev_boundary(&self) -> Option<usize> {
+        self.command[..self.cursor].grapheme_indices(true).next_back().map(|(i, _)| i)
+    }
+
+    /// End of the grapheme after the cursor
+    fn next_boundary(&self) -> Option<usize> {
+        self.command[self.cursor..].graphemes(true).next().map(|g| self.cursor + g.len())
+    }
+
+    fn backspace(&mut self) {
+        if let Some(i) = self.prev_boundary() {
+            self.command.drain(i..self.cursor);
+            self.cursor = i;
+        }
+    }
+
+    fn delete(&mut self) {
+        if let Some(end) = self.next_boundary() {
+            self.command.drain(self.cursor..end);
+        }
+    }
+
+    fn cursor_left(&mut self) {
+        self.cursor = self.prev_boundary().unwrap_or(self.cursor);
+    }
+
+    fn cursor_right(&mut self) {
+        self.cursor = self.next_boundary().unwrap_or(self.cursor);
+    }
+
+    /// Empties the command box; a non-empty command joins the history
+    /// unless it repeats the newest entry
+    fn take_command(&mut self) -> String {
+        self.cursor = 0;
+        self.history_index = None;
+        self.draft.clear();
+        let cmd = std::mem::take(&mut self.command);
+        let entry = cmd.trim();
+        if !entry.is_empty() && self.history.last().is_none_or(|last| last != entry) {
+            if self.history.len() == COMMAND_HISTORY {
+                self.history.remove(0);
+            }
+            self.history.push(entry.to_string());
+        }
+        cmd
+    }
+
+    /// Up: the previous history entry into the command box
+    fn history_back(&mut self) {
+        let index = match self.history_index {
+            _ if self.history.is_empty() => return,
+            None => {
+                self.draft = self.command.clone();
+                self.history.len() - 1
+            }
+            Some(i) => i.saturating_sub(1),
+        };
+        self.recall(Some(index));
+    }
+
+    /// Down: the next entry, then back to what was being typed
+    fn history_forward(&mut self) {
+        match self.history_index {
+            None => {}
+            Some(i) if i + 1 < self.history.len() => self.recall(Some(i + 1)),
+            Some(_) => self.recall(None),
+        }
+    }
+
+    fn recall(&mut self, index: Option<usize>) {
+        self.history_index = index;
+        self.command = match index {
+            Some(i) => self.history[i].clone(),
+            None => std::mem::take(&mut self.draft),
+        };
+        self.cursor = self.command.len();
+    }
+
+    /// Move the focused pane `rows` lines toward older output / events
+    fn scroll_up(&mut self, rows: usize) {
+        match self.focus {
+            Focus::Output => self.selected = self.selected.saturating_sub(rows),
+            Focus::Events => self.events_scroll = (self.events_scroll + rows).min(self.max_events_scroll()),
+        }
+    }
+
+    fn scroll_down(&mut self, rows: usize) {
+        match self.focus {
+            Focus::Output => self.selected = self.selected.saturating_add(rows),
+            Focus::Events => self.events_scroll = self.events_scroll.saturating_sub(rows),
         }
     }
 }
@@ -70,27 +459,62 @@ impl App {
 // =======================
 // ENTRY
 // =======================
-pub fn run() -> io::Result<()> {
+/// Start the TUI; `initial_command` (e.g. `scan 10.0.0.1 80`) runs once
+/// on startup as if typed at the prompt. `inline` draws in the normal
+/// screen instead of the alternate one, so the last frame stays in the
+/// scrollback after exit.
+pub fn run(initial_command: Option<String>, color: bool, inline: bool) -> io::Result<()> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    let viewport = if inline {
+        Viewport::Inline(crossterm::terminal::size()?.1)
+    } else {
+        execute!(stdout, EnterAlternateScreen)?;
+        Viewport::Fullscreen
+    };
 
     let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let mut terminal = Terminal::with_options(backend, TerminalOptions { viewport })?;
 
     let mut app = App::new();
+    app.color = color;
     app.event("WISE1738 ready");
-    app.event("Commands: scan -a <host> -p <ports> | export json | export pdf | exit");
+    load_default_ports(&mut app);
+    app.event("Commands: scan <host> [ports|all] [udp] | export json | export xml [path] | export pdf | watch <host> [ports] --interval <dur> | exit");
+
+    match initial_command {
+        Some(cmd) => handle_command(cmd.trim(), &mut app),
+        // Nothing to run yet: say what can be
+        None => show_help(&mut app),
+    }
 
     let res = event_loop(&mut terminal, &mut app);
 
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    if inline {
+        // Leave the prompt on a fresh line under the last frame
+        let bottom = terminal.get_frame().size().bottom();
+        terminal.set_cursor(0, bottom.saturating_sub(1))?;
+        println!();
+    } else {
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    }
     terminal.show_cursor()?;
 
     res
 }
 
+fn load_default_ports(app: &mut App) {
+    match cli::env_default_ports() {
+        Some(Ok(p)) => {
+            app.event(format!("Default ports from {}: {} ports", PORTS_ENV, p.ports.len()));
+            app.default_ports = p;
+        }
+        Some(Err(e)) => app.warn(format!("Ignoring {} ({}), default is top_basic", PORTS_ENV, e)),
+        None => {}
+    }
+}
+
 // =======================
 // EVENT LOOP
 // =======================
@@ -99,34 +523,93 @@ fn event_loop(
     app: &mut App,
 ) -> io::Result<()> {
     loop {
+        poll_scan(app);
+        tick_watch(app);
         terminal.draw(|f| draw_ui(f, app))?;
+        // Not mid-scan: output is still growing and a kept position
+        // would collapse to 0 before the rows arrive
+        if !matches!(app.state, UiState::Scanning) {
+            app.selected = app.selected.min(app.max_scroll.get());
+        }
+        app.events_scroll = app.events_scroll.min(app.max_events_scroll());
 
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char(c) => app.command.push(c),
-                    KeyCode::Backspace => {
-                        app.command.pop();
-                    }
-                    KeyCode::Up => {
-                        if app.scroll > 0 {
-                            app.scroll -= 1;
-                        }
+        if event::poll(Duration::from_millis(100))?
+            && let event::Event::Key(key) = event::read()?
+        {
+            app.last_error = false;
+
+            if matches!(app.state, UiState::Detail) {
+                if matches!(key.code, KeyCode::Esc | KeyCode::Enter) {
+                    app.state = UiState::Idle;
+                }
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Esc if matches!(app.state, UiState::Scanning) => cancel_scan(app),
+                KeyCode::Esc if app.watch.is_some() => stop_watch(app),
+                // Alt+P: hold off new connects / go on where the scan stopped
+                KeyCode::Char('p')
+                    if key.modifiers.contains(KeyModifiers::ALT) && matches!(app.state, UiState::Scanning) =>
+                {
+                    toggle_pause(app);
+                }
+                // Alt+O / Alt+C: jump to the OPEN / CLOSED section
+                KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::ALT) => {
+                    if let (Some(line), _) = app.sections.get() {
+                        app.selected = line;
                     }
-                    KeyCode::Down => {
-                        app.scroll = app.scroll.saturating_add(1);
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::ALT) => {
+                    if let (_, Some(line)) = app.sections.get() {
+                        app.selected = line;
                     }
-                    KeyCode::Enter => {
-                        let cmd = app.command.trim().to_string();
-                        app.command.clear();
+                }
+                KeyCode::Char(c) => app.insert(c),
+                KeyCode::Backspace => app.backspace(),
+                KeyCode::Delete => app.delete(),
+                KeyCode::Left => app.cursor_left(),
+                KeyCode::Right => app.cursor_right(),
+                KeyCode::Home => app.cursor = 0,
+                KeyCode::End => app.cursor = app.command.len(),
+                KeyCode::Tab => {
+                    app.focus = match app.focus {
+                        Focus::Output => Focus::Events,
+                        Focus::Events => Focus::Output,
+                    };
+                }
+                // Ctrl+Up/Down and PgUp/PgDn scroll; plain Up/Down are history
+                KeyCode::Up if key.modifiers.contains(KeyModifiers::CONTROL) => app.scroll_up(1),
+                KeyCode::Down if key.modifiers.contains(KeyModifiers::CONTROL) => app.scroll_down(1),
+                KeyCode::PageUp => app.scroll_up(PAGE_ROWS),
+                KeyCode::PageDown => app.scroll_down(PAGE_ROWS),
+                KeyCode::Up => app.history_back(),
+                KeyCode::Down => app.history_forward(),
+                // Empty prompt + a selected row: show its details
+                KeyCode::Enter
+                    if app.command.trim().is_empty()
+                        && matches!(app.state, UiState::Idle)
+                        && app.focus == Focus::Output
+                        && selected_result(app).is_some() =>
+                {
+                    app.state = UiState::Detail;
+                }
+                KeyCode::Enter => {
+                    let cmd = app.take_command().trim().to_string();
 
-                        match app.state {
-                            UiState::ExitPending => return Ok(()),
-                            UiState::Idle => handle_command(&cmd, app),
+                    match app.state {
+                        UiState::ExitPending => return Ok(()),
+                        UiState::Scanning if cmd == "cancel" => cancel_scan(app),
+                        UiState::Scanning => app.error("Scan in progress (Esc or cancel stops it)"),
+                        // Exports and saves read the last run; anything else would race the next one
+                        UiState::Watching if cmd.starts_with("export") || cmd.starts_with("save") => {
+                            handle_command(&cmd, app)
                         }
+                        UiState::Watching => app.error("Watching (Esc stops)"),
+                        UiState::Idle | UiState::Detail => handle_command(&cmd, app),
                     }
-                    _ => {}
                 }
+                _ => {}
             }
         }
     }
@@ -147,126 +630,894 @@ fn handle_command(cmd: &str, app: &mut App) {
             app.event("Exit requested");
             app.state = UiState::ExitPending;
         }
-        ["export", "json"] => export_json(app),
+        ["export", "json"] => export_json(app, false, None),
+        ["export", "json", "pretty"] => export_json(app, true, None),
+        ["export", "json", "pretty", path] => export_json(app, true, Some(path)),
+        ["export", "json", path] => export_json(app, false, Some(path)),
+        ["export", "csv"] => export_csv(app, None),
+        ["export", "csv", path] => export_csv(app, Some(path)),
+        ["export", "xml"] => export_xml(app, None),
+        ["export", "xml", path] => export_xml(app, Some(path)),
         ["export", "pdf"] => export_pdf(app),
+        ["save", path] => save_report(app, path),
+        ["save", ..] => app.error("Usage: save <path>"),
+        ["load", path] => load_report(app, path),
+        ["load", ..] => app.error("Usage: load <path>"),
         ["scan", ..] => handle_scan(parts, app),
-        _ => app.event("Unknown command"),
+        ["watch", ..] => handle_watch(parts, app),
+        ["rescan-open"] => rescan_open(app),
+        ["help"] | ["?"] => show_help(app),
+        ["cancel"] => app.error("No scan running"),
+        ["ports"] => app.error("Usage: ports <expr>  (e.g. ports web,8000-8010,!8005)"),
+        ["ports", spec @ ..] => preview_ports(&spec.join(","), app),
+        ["view", "open"] => {
+            app.help = false;
+            app.view = View::Open;
+            app.selected = 0;
+            app.event("View: open ports only");
+        }
+        ["view", "all"] => {
+            app.help = false;
+            app.view = View::All;
+            app.event("View: all ports");
+        }
+        ["view", "summary"] => {
+            app.help = false;
+            app.view = View::Summary;
+            app.selected = 0;
+            app.event("View: one line per host");
+        }
+        ["view", ..] => app.error("Usage: view open|all|summary"),
+        ["filter"] => app.error("Usage: filter <text>|clear"),
+        ["filter", "clear"] => {
+            app.filter = None;
+            app.selected = 0;
+            app.event("Filter cleared");
+        }
+        ["filter", ..] => set_filter(cmd["filter".len()..].trim(), app),
+        ["set"] => {
+            let settings = settings_line(app);
+            app.event(settings);
+        }
+        ["set", "timeout", raw] => {
+            // Bare numbers are milliseconds here: `set timeout 2000`
+            let t = raw
+                .parse::<u64>()
+                .ok()
+                .map(Duration::from_millis)
+                .or_else(|| cli::parse_duration(raw))
+                .filter(|t| !t.is_zero());
+            match t {
+                Some(t) => {
+                    app.config.timeout = Some(t);
+                    app.event(format!("Timeout: {}ms", t.as_millis()));
+                }
+                None => app.error("Usage: set timeout <ms|dur>  (e.g. 2000, 2s)"),
+            }
+        }
+        ["set", "retries", n] => match n.parse::<u8>() {
+            Ok(n) => {
+                app.config.retries = n;
+                app.event(format!("Retries: {}", n));
+            }
+            Err(_) => app.error("Usage: set retries <0-255>"),
+        },
+        ["set", "workers", n] => match cli::parse_workers(n) {
+            Some(n) => {
+                app.config.workers = Some(n);
+                app.event(format!("Workers: {}", n));
+            }
+            None => app.error("Usage: set workers <n>  (n >= 1)"),
+        },
+        ["set", "rate", "off"] => {
+            app.config.max_rate = None;
+            app.event("Rate: unlimited");
+        }
+        ["set", "rate", n] => match cli::parse_rate(n) {
+            Some(n) => {
+                app.config.max_rate = Some(n);
+                app.event(format!("Rate: {} connects/sec", n));
+            }
+            None => app.error("Usage: set rate <n>|off  (connects/sec, n >= 1)"),
+        },
+        ["set", "family", f] => match AddressFamily::parse(f) {
+            Some(family) => {
+                app.config.family = family;
+                app.event(format!("Address family: {}", family));
+            }
+            None => app.error("Usage: set family any|v4|v6"),
+        },
+        ["set", "timeout" | "retries" | "workers" | "rate" | "family", ..] => {
+            app.error("Usage: set timeout <ms|dur> | set retries <n> | set workers <n> | set rate <n>|off | set family any|v4|v6")
+        }
+        ["banners", "on"] => {
+            app.config.banners = true;
+            app.event(format!("Banners: on (first {} bytes of each open port)", scanner::BANNER_BYTES));
+        }
+        ["banners", "off"] => {
+            app.config.banners = false;
+            app.event("Banners: off");
+        }
+        ["banners", ..] => app.error("Usage: banners on|off"),
+        ["set", "events", n] => match n.parse::<usize>() {
+            Ok(n) if n > 0 => {
+                app.event_cap = n;
+                app.event(format!("Event history: {} entries", n));
+            }
+            _ => app.error("Usage: set events <n>  (n >= 1)"),
+        },
+        ["set", "scroll", mode @ ("keep" | "reset")] => {
+            app.keep_scroll = *mode == "keep";
+            app.event(format!("Output scroll on rescan: {}", mode));
+        }
+        ["set", "scroll", ..] => app.error("Usage: set scroll keep|reset"),
+        ["set", "timefmt", ..] => set_time_format(cmd, app),
+        _ => app.error("Unknown command"),
     }
 }
 
+/// `set timefmt <fmt>`: the rest of the line, quotes optional, since
+/// formats usually contain spaces. `iso` is short for ISO-8601.
+fn set_time_format(cmd: &str, app: &mut App) {
+    let raw = cmd
+        .trim_start()
+        .strip_prefix("set")
+        .map(str::trim_start)
+        .and_then(|rest| rest.strip_prefix("timefmt"))
+        .map(str::trim)
+        .unwrap_or("");
+    let format = match raw.trim_matches('"') {
+        "iso" => "%Y-%m-%dT%H:%M:%S%:z",
+        f => f,
+    };
+
+    // Formatting with a bad specifier panics, so check it up front
+    let invalid = StrftimeItems::new(format).any(|item| matches!(item, Item::Error));
+    if format.is_empty() || invalid {
+        app.error("Usage: set timefmt <strftime format>  (e.g. \"%Y-%m-%d %H:%M:%S\", iso)");
+        return;
+    }
+    app.time_format = format.to_string();
+    app.event(format!("Event time format: {}", format));
+}
+
 // =======================
-// SCAN HANDLER (FLAG-BASED)
+// SCAN HANDLER (FLAGS + POSITIONAL)
 // =======================
 fn handle_scan(parts: Vec<&str>, app: &mut App) {
-    let mut host = None;
-    let mut ports = None;
-
-    // ✅ Host (-a) va ports (-p) flaglarini aniqlash
-    let mut i = 1; // 0 index = "scan"
-    while i < parts.len() {
-        match parts[i] {
-            "-a" => {
-                host = parts.get(i + 1).copied();
-                i += 2;
-            }
-            "-p" => {
-                ports = parts.get(i + 1).copied();
-                i += 2;
-            }
-            _ => i += 1,
-        }
+    let Some((targets, ports, config)) = scan_request(&parts[1..], app) else {
+        return;
+    };
+
+    if !app.keep_scroll {
+        app.selected = 0;
     }
+    app.last_results.clear();
+    start_scan(app, targets, ports, config);
+}
+
+/// Rows stay in `last_results`; the table just skips the ones without `text`
+fn set_filter(text: &str, app: &mut App) {
+    let filter = text.to_lowercase();
+    let total = app.last_results.len();
+    let shown = app.last_results.iter().filter(|r| matches_filter(r, &filter)).count();
+    app.filter = Some(filter);
+    app.help = false;
+    app.selected = 0;
+    app.event(format!("Filter '{}': {} of {} results match (filter clear resets)", text, shown, total));
+}
+
+fn show_help(app: &mut App) {
+    app.help = true;
+    app.selected = 0;
+    app.event("Help in the output pane; a scan or view command returns to results");
+}
+
+/// Targets, ports and settings of a `scan`-style command line; errors
+/// are reported and give `None`
+fn scan_request(args: &[&str], app: &mut App) -> Option<(Vec<Target>, Ports, ScanConfig)> {
+    let cache = &mut app.port_cache;
+    let args = match ScanArgs::parse_with_ports(args, &app.config, |spec| cached_ports(cache, spec)) {
+        Ok(a) => a,
+        Err(e) => {
+            app.error(e);
+            return None;
+        }
+    };
+    let expanded = args.targets();
+    let (host, config) = (args.hosts, args.config);
 
-    let host = match host {
-        Some(h) => h,
+    let ports = match args.ports {
+        Some(p) => p,
         None => {
-            app.event("Usage: scan -a <ip|domain> -p <ports>");
-            return;
+            app.event(format!(
+                "No ports given, using default set ({} ports)",
+                app.default_ports.ports.len()
+            ));
+            let mut ports = app.default_ports.clone().with_protocol(args.protocol);
+            ports.exclude(&args.skip);
+            ports
         }
     };
 
-    let ports = match ports {
-        Some(p) => match parse_ports(p) {
-            Some(p) => p,
-            None => {
-                app.event("Invalid port format");
+    let targets = match expanded {
+        Ok(t) if !t.is_empty() => t,
+        Ok(_) => {
+            app.error("Usage: scan <host[,host...]|cidr> [ports] [!ports] [udp]");
+            return None;
+        }
+        Err(e) => {
+            app.error(format!("Invalid target: {}", e));
+            return None;
+        }
+    };
+
+    if targets.len() > 1 {
+        app.event(format!("Scanning {} hosts", targets.len()));
+    } else {
+        app.event(format!("Scanning {}", host));
+    }
+    Some((targets, ports, config))
+}
+
+// =======================
+// WATCH (RESCAN ON AN INTERVAL)
+// =======================
+/// Interval when `watch` is given none
+const WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// `watch <host> [ports] [flags] [--interval <dur>]`: rescan every
+/// interval and report ports that opened or closed since the last run
+fn handle_watch(parts: Vec<&str>, app: &mut App) {
+    let mut args = parts[1..].to_vec();
+    let mut interval = WATCH_INTERVAL;
+    if let Some(i) = args.iter().position(|a| *a == "--interval") {
+        let raw = args.get(i + 1).copied().unwrap_or("");
+        match cli::parse_duration(raw) {
+            Some(d) if d >= Duration::from_secs(1) => interval = d,
+            _ => {
+                app.error(format!("Invalid --interval '{}' (at least 1s, e.g. 30s, 5m)", raw));
                 return;
             }
-        },
-        None => Ports::all(),
+        }
+        args.drain(i..(i + 2).min(args.len()));
+    }
+
+    let Some((targets, ports, config)) = scan_request(&args, app) else {
+        return;
     };
+    app.event(format!("Watching every {}s (Esc stops)", interval.as_secs()));
+    app.watch = Some(Watch {
+        interval,
+        next: Instant::now(),
+        targets,
+        ports,
+        config,
+        previous: None,
+        runs: 0,
+    });
+    app.changed.clear();
+    app.state = UiState::Watching;
+}
 
-    app.open.clear();
-    app.closed.clear();
-    app.scroll = 0;
+/// Start the next watch run once its time has come
+fn tick_watch(app: &mut App) {
+    if !matches!(app.state, UiState::Watching) {
+        return;
+    }
+    let Some(watch) = &mut app.watch else {
+        return;
+    };
+    if Instant::now() < watch.next {
+        return;
+    }
+
+    // Runs start on the interval, not an interval after the last one ended
+    watch.next += watch.interval;
+    let (targets, ports, config) = (watch.targets.clone(), watch.ports.clone(), watch.config.clone());
+    if !app.keep_scroll {
+        app.selected = 0;
+    }
     app.last_results.clear();
+    start_scan(app, targets, ports, config);
+}
 
-    app.event(format!("Scanning {}", host));
+/// Compare a finished watch run with the previous one
+fn watch_changes(app: &mut App) {
+    let Some(watch) = &mut app.watch else {
+        return;
+    };
+    let open: Vec<ScanResult> = app
+        .last_results
+        .iter()
+        .filter(|r| r.status == PortStatus::Open)
+        .cloned()
+        .collect();
+    watch.runs += 1;
+    let runs = watch.runs;
+    // A run longer than the interval: the next one starts right away,
+    // without trying to catch up on the missed ones
+    watch.next = watch.next.max(Instant::now());
+    let Some(previous) = watch.previous.replace(open) else {
+        return;
+    };
 
-    let results = engine::run(host, ports);
+    let (opened, closed) = scanner::open_changes(&previous, watch.previous.as_deref().unwrap_or_default());
+    if opened.is_empty() && closed.is_empty() {
+        app.event(format!("Watch run {}: no changes", runs));
+    }
+    for (host, port) in &opened {
+        app.warn(format!("Watch run {}: {}:{} opened", runs, app.host_name(host), port));
+    }
+    for (host, port) in &closed {
+        app.warn(format!("Watch run {}: {}:{} closed", runs, app.host_name(host), port));
+    }
+    app.changed = opened.into_iter().chain(closed).collect();
+}
+
+fn stop_watch(app: &mut App) {
+    app.watch = None;
+    if matches!(app.state, UiState::Watching) {
+        app.state = UiState::Idle;
+    }
+    app.event("Watch stopped");
+}
 
-    for r in &results {
-        let mut service = String::new();
+/// Second pass over the ports the last scan found open, with full
+/// service detection, on the hosts that had any
+fn rescan_open(app: &mut App) {
+    let Some((targets, config)) = app.last_scan.clone() else {
+        app.error("Nothing to rescan yet");
+        return;
+    };
+
+    let open: Vec<&ScanResult> = app
+        .last_results
+        .iter()
+        .filter(|r| r.status == PortStatus::Open)
+        .collect();
+    if open.is_empty() {
+        app.error("No open ports to rescan");
+        return;
+    }
+
+    let mut ports: Vec<u16> = open.iter().map(|r| r.port).collect();
+    ports.sort_unstable();
+    ports.dedup();
+    // A scan covers one protocol, so the first result's is everyone's
+    let ports = Ports::multiple(ports).with_protocol(open[0].protocol);
+    let targets: Vec<Target> = targets
+        .into_iter()
+        .filter(|t| open.iter().any(|r| r.host == t.host))
+        .collect();
+
+    app.event(format!(
+        "Rescanning {} open port(s) on {} host(s) with full probes",
+        ports.ports.len(),
+        targets.len()
+    ));
+    let config = ScanConfig {
+        connect_only: false,
+        ..config
+    };
+    if !app.keep_scroll {
+        app.selected = 0;
+    }
+    app.last_results.clear();
+    start_scan(app, targets, ports, config);
+}
+
+/// Parsed `spec`, from the cache when it is the spec parsed last time
+fn cached_ports(cache: &mut Option<(String, Ports)>, spec: &str) -> Result<Ports, PortParseError> {
+    if let Some((key, ports)) = cache
+        && key == spec
+    {
+        return Ok(ports.clone());
+    }
+    let ports = Ports::parse(spec)?;
+    *cache = Some((spec.to_string(), ports.clone()));
+    Ok(ports)
+}
 
-        if r.service != "unknown" {
-            service.push_str(r.service);
+/// Run the scan on a background thread; results stream in via `poll_scan`
+fn start_scan(app: &mut App, targets: Vec<Target>, ports: Ports, config: ScanConfig) {
+    if config.connect_only {
+        app.event("Connect-only: no probes, no payload sent");
+    }
+
+    let workers = config.effective_workers(&ports);
+    match config.runs_async(&ports) {
+        true => app.event(format!("Workers: {} connects in flight", workers)),
+        false => app.event(format!("Workers: {} threads", workers)),
+    }
+    app.help = false;
+    app.multi_host = targets.len() > 1;
+    app.last_scan = Some((targets.clone(), config.clone()));
+    app.last_meta = None;
+    app.pending = ports.ports.iter().map(|&p| (p, targets.len())).collect();
+    app.pause.resume();
+    app.cancel = CancelFlag::default();
+    let (progress, progress_rx) = mpsc::channel();
+    app.gauge = Some(ScanGauge {
+        rx: progress_rx,
+        done: BTreeMap::new(),
+        total: ports.ports.len() * targets.len(),
+    });
+    let config = ScanConfig {
+        pause: app.pause.clone(),
+        progress: Some(progress),
+        cancel: app.cancel.clone(),
+        ..config
+    };
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut sink = ChannelSink {
+            tx: tx.clone(),
+            sent: 0,
+            cap: config.result_cap(),
+            open_only: config.open_only,
+        };
+        // The sink applies open_only, so hidden ports still count as done
+        let config = ScanConfig {
+            open_only: false,
+            ..config
+        };
+        let meta = engine::run_into(&targets, &ports, &config, &mut sink);
+        let _ = tx.send(ScanMsg::Done(meta));
+    });
+
+    app.scan_rx = Some(rx);
+    app.spinner = 0;
+    app.state = UiState::Scanning;
+}
+
+/// Stop the running scan (and a watch driving it); the results that
+/// arrived so far are shown once the workers wind down
+fn cancel_scan(app: &mut App) {
+    if app.cancel.is_cancelled() {
+        return;
+    }
+    app.cancel.cancel();
+    app.event("Cancelling scan…");
+    if app.watch.is_some() {
+        stop_watch(app);
+    }
+}
+
+/// Pause or resume the running scan; results so far stay
+fn toggle_pause(app: &mut App) {
+    if app.pause.is_paused() {
+        app.pause.resume();
+        app.event("Scan resumed");
+    } else {
+        app.pause.pause();
+        app.event("Scan paused (Alt+P resumes)");
+    }
+}
+
+// =======================
+// BACKGROUND SCAN
+// =======================
+/// Messages from the scan thread
+enum ScanMsg {
+    Result(ScanResult),
+    /// A port finished but open_only hides its result
+    Hidden(u16),
+    Done(ScanMeta),
+}
+
+/// Forwards each result to the UI thread as it arrives; the UI keeps
+/// them all, so the config's result cap applies
+struct ChannelSink {
+    tx: Sender<ScanMsg>,
+    sent: usize,
+    cap: usize,
+    open_only: bool,
+}
+
+/// Per-port progress of the running scan. It has its own channel, which
+/// `poll_scan` empties every tick, so it never waits on the result rows.
+struct ScanGauge {
+    rx: Receiver<ScanProgress>,
+    /// Ports done per host (hosts are scanned one after another)
+    done: BTreeMap<String, usize>,
+    /// Ports times hosts
+    total: usize,
+}
+
+impl ScanGauge {
+    /// Take every update since the last tick; the latest per host wins
+    fn drain(&mut self) {
+        while let Ok(p) = self.rx.try_recv() {
+            self.done.insert(p.host, p.completed);
         }
+    }
 
-        if let Some(os) = r.os_hint {
-            if !service.is_empty() {
-                service.push(' ');
-            }
-            service.push_str(&format!("[{}]", os));
+    fn completed(&self) -> usize {
+        self.done.values().sum()
+    }
+
+    fn ratio(&self) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => (self.completed() as f64 / total as f64).min(1.0),
+        }
+    }
+}
+
+impl ResultSink for ChannelSink {
+    fn on_result(&mut self, result: &ScanResult) {
+        if self.open_only && result.status != PortStatus::Open {
+            let _ = self.tx.send(ScanMsg::Hidden(result.port));
+        } else if self.sent < self.cap {
+            self.sent += 1;
+            let _ = self.tx.send(ScanMsg::Result(result.clone()));
         }
+    }
 
-        match r.status {
-            PortStatus::Open => {
-                app.open.push(format!("{:<5} OPEN   {}", r.port, service));
+    fn wants_more(&self) -> bool {
+        self.sent < self.cap
+    }
+}
+
+/// Drain whatever the scan thread sent since the last tick (at most
+/// [`ROWS_PER_TICK`]), so the UI redraws once per tick, not per port
+fn poll_scan(app: &mut App) {
+    if app.scan_rx.is_none() {
+        return;
+    }
+    if !app.pause.is_paused() {
+        app.spinner = (app.spinner + 1) % SPINNER.len();
+    }
+    if let Some(gauge) = &mut app.gauge {
+        gauge.drain();
+    }
+
+    for _ in 0..ROWS_PER_TICK {
+        let Some(rx) = &app.scan_rx else {
+            return;
+        };
+
+        match rx.try_recv() {
+            Ok(ScanMsg::Result(r)) => {
+                app.port_done(r.port);
+                app.last_results.push(r);
             }
-            _ => {
-                app.closed.push(format!("{:<5} CLOSED {}", r.port, service));
+            Ok(ScanMsg::Hidden(port)) => app.port_done(port),
+            Ok(ScanMsg::Done(meta)) => {
+                app.scan_rx = None;
+                app.pending.clear();
+                app.gauge = None;
+                app.state = app.idle_state();
+                finish_scan(app, meta);
             }
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                app.scan_rx = None;
+                app.pending.clear();
+                app.gauge = None;
+                app.state = app.idle_state();
+                app.log(Level::Error, "Scan aborted");
+            }
+        }
+    }
+}
+
+/// Rows arrive in completion order; once done, re-sort by host (scan
+/// order) and port
+fn finish_scan(app: &mut App, meta: ScanMeta) {
+    let mut results = std::mem::take(&mut app.last_results);
+    scanner::sort_results(&mut results);
+
+    let skipped = results.iter().filter(|r| r.status == PortStatus::Skipped).count();
+    if skipped > 0 {
+        app.warn(format!("{} ports skipped (host time budget ran out)", skipped));
+    }
+
+    for e in &meta.errors {
+        app.warn(format!("Warning: {}", e));
+    }
+
+    // open_only hid the closed/filtered ports a verdict is made of
+    if app.last_scan.as_ref().is_some_and(|(_, c)| !c.open_only) {
+        for line in cli::verdict_lines(&scanner::host_verdicts(&mut results)) {
+            app.event(line);
         }
     }
 
+    for info in &meta.hosts {
+        app.event(format!("{}: {}", app.host_name(&info.host), info));
+    }
+
     app.last_results = results;
-    app.event("Scan finished");
+    app.event(format!("Scan of {} finished in {}ms", meta.target, meta.duration_ms));
+    app.event(format!("Metrics: {}", meta.metrics));
+    if let Some((_, config)) = &app.last_scan
+        && let Some(hint) = meta.metrics.filtered_hint(config)
+    {
+        app.warn(format!("Hint: {}", hint));
+    }
+    app.last_meta = Some(meta);
+
+    match app.watch {
+        Some(_) => watch_changes(app),
+        None => app.changed.clear(),
+    }
+}
+
+/// Prompt line fitting `width` columns, and the cursor column. Widths
+/// are display columns (CJK counts two); when the command is too long
+/// the line scrolls so the cursor stays visible.
+fn command_line(command: &str, cursor: usize, width: usize) -> (String, usize) {
+    let mut shown = format!("> {}", command);
+    let mut column = shown[..cursor + 2].width();
+
+    while column >= width.max(1)
+        && let Some(first) = shown.graphemes(true).next()
+    {
+        column -= first.width();
+        shown.drain(..first.len());
+    }
+    (shown, column)
+}
+
+/// `pending: 1234 (5000-6233, 8080, …)` for the status bar: ports still
+/// waiting on a result (per host), and the first few ranges they form
+fn pending_text(pending: &BTreeMap<u16, usize>) -> String {
+    const SHOWN: usize = 3;
+
+    let left: usize = pending.values().sum();
+    let ranges = ports::ranges(pending.keys().copied());
+    let mut text = range_labels(&ranges[..ranges.len().min(SHOWN)]);
+    if ranges.len() > SHOWN {
+        text.push("…".into());
+    }
+    match left {
+        0 => "pending: 0".into(),
+        n => format!("pending: {} ({})", n, text.join(", ")),
+    }
+}
+
+/// `80`, `8000-8010`
+fn range_labels(ranges: &[(u16, u16)]) -> Vec<String> {
+    ranges
+        .iter()
+        .map(|&(a, b)| if a == b { a.to_string() } else { format!("{}-{}", a, b) })
+        .collect()
+}
+
+/// `ports <expr>`: what a port expression expands to, without scanning
+fn preview_ports(spec: &str, app: &mut App) {
+    match Ports::parse(spec) {
+        Ok(p) => {
+            let list = range_labels(&ports::ranges(p.ports.iter().copied())).join(",");
+            app.event(format!("{} ports: {}", p.ports.len(), list));
+        }
+        Err(e) => app.error(format!("Invalid port spec: {}", e)),
+    }
+}
+
+/// `set` with no arguments: every value it can change
+fn settings_line(app: &App) -> String {
+    let workers = app.config.workers.map_or("auto".to_string(), |n| n.to_string());
+    let rate = app.config.max_rate.map_or("unlimited".to_string(), |n| format!("{}/s", n));
+    format!(
+        "Settings: timeout {}ms, retries {}, workers {}, rate {}, family {}, banners {}, events {}, scroll {}, timefmt {}",
+        app.config.connect_timeout().as_millis(),
+        app.config.retries,
+        workers,
+        rate,
+        app.config.family,
+        if app.config.banners { "on" } else { "off" },
+        app.event_cap,
+        if app.keep_scroll { "keep" } else { "reset" },
+        app.time_format
+    )
+}
+
+/// STATE column label; no catch-all, so a new status needs its own
+fn state_label(status: PortStatus) -> &'static str {
+    match status {
+        PortStatus::Open => "OPEN",
+        PortStatus::Closed => "CLOSED",
+        PortStatus::Filtered => "FILTERED",
+        PortStatus::Skipped => "SKIPPED",
+        PortStatus::ProxyDenied => "DENIED",
+        PortStatus::OpenFiltered => "OPEN|FILTERED",
+        PortStatus::Unreachable => "UNREACHABLE",
+    }
+}
+
+/// RTT column: `0.3ms` under 10ms, whole milliseconds above
+fn latency_text(latency: Duration) -> String {
+    let ms = latency.as_secs_f64() * 1000.0;
+    match ms < 10.0 {
+        true => format!("{:.1}ms", ms),
+        false => format!("{:.0}ms", ms),
+    }
+}
+
+/// SERVICE column: name, OS hint, probe detail, local address
+fn service_text(r: &ScanResult) -> String {
+    let mut service = String::new();
+
+    if r.service != "unknown" {
+        service.push_str(&r.service);
+    }
+
+    if let Some(os) = r.os_hint {
+        if !service.is_empty() {
+            service.push(' ');
+        }
+        service.push_str(&format!("[{}]", os));
+    }
+
+    if let Some(detail) = &r.detail {
+        service.push_str(&format!(" {}", detail));
+    }
+
+    if let Some(local) = r.local_addr {
+        service.push_str(&format!(" via {}", local.ip()));
+    }
+
+    if let Some(banner) = &r.banner {
+        service.push_str(&format!(" \"{}\"", banner));
+    }
+
+    service
+}
+
+// =======================
+// SAVE / LOAD
+// =======================
+/// Last results with their scan's target and start time, for `load`
+fn save_report(app: &mut App, path: &str) {
+    let Some(meta) = app.last_meta.as_ref().filter(|_| !app.last_results.is_empty()) else {
+        app.warn("Nothing to save");
+        return;
+    };
+    let report = ScanReport {
+        target: meta.target.clone(),
+        started_at: meta.started_at,
+        duration: Duration::from_millis(u64::try_from(meta.duration_ms).unwrap_or(u64::MAX)),
+        results: app.last_results.clone(),
+        errors: meta.errors.clone(),
+    };
+
+    match fs::write(path, report::report_json(&report)) {
+        Ok(_) => app.event(format!("Saved {} results → {}", report.results.len(), path)),
+        Err(e) => app.error(format!("Save failed: {}", e)),
+    }
+}
+
+/// Saved results take the place of the last scan's, as if it had just
+/// finished: view, export and rescan-open work on them
+fn load_report(app: &mut App, path: &str) {
+    let loaded = fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| report::parse_report(&text).map_err(|e| e.to_string()));
+    let mut report = match loaded {
+        Ok(r) => r,
+        Err(e) => {
+            app.error(format!("Cannot load {}: {}", path, e));
+            return;
+        }
+    };
+    scanner::sort_results(&mut report.results);
+
+    let mut targets: Vec<Target> = Vec::new();
+    for r in &report.results {
+        if !targets.iter().any(|t| t.host == r.host) {
+            targets.push(Target::new(&r.host));
+        }
+    }
+    app.help = false;
+    app.selected = 0;
+    app.changed.clear();
+    app.multi_host = targets.len() > 1;
+    app.last_scan = Some((targets, app.config.clone()));
+
+    app.event(format!(
+        "Loaded {} results from {} (scan of {}, {})",
+        report.results.len(),
+        path,
+        report.target,
+        report.started_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+    ));
+    // Counts come back from the results; connects, timeouts and TTLs
+    // were never saved
+    let mut metrics = ScanMetrics { elapsed: report.duration, ..ScanMetrics::default() };
+    for r in &report.results {
+        metrics.count(r);
+    }
+    app.event(format!("Metrics: {}", metrics));
+    app.last_meta = Some(ScanMeta {
+        target: report.target,
+        started_at: report.started_at,
+        duration_ms: report.duration.as_millis(),
+        errors: report.errors,
+        metrics,
+        hosts: Vec::new(),
+    });
+    app.last_results = report.results;
 }
 
 // =======================
 // EXPORT JSON
 // =======================
-fn export_json(app: &mut App) {
+fn export_json(app: &mut App, pretty: bool, path: Option<&str>) {
     if app.last_results.is_empty() {
-        app.event("Nothing to export");
+        app.warn("Nothing to export");
         return;
     }
 
-    fs::create_dir_all("export").ok();
-    let file = format!("export/scan_{}.json", Local::now().format("%Y%m%d_%H%M%S"));
-
-    let mut json = String::from("{\"results\":[");
-    for (i, r) in app.last_results.iter().enumerate() {
-        json.push_str(&format!(
-            "{{\"port\":{},\"status\":\"{:?}\",\"service\":\"{}\",\"os\":{},\"confidence\":{}}}",
-            r.port,
-            r.status,
-            r.service,
-            match r.os_hint {
-                Some(os) => format!("\"{}\"", os),
-                None => "null".into(),
-            },
-            r.confidence
-        ));
-        if i + 1 < app.last_results.len() {
-            json.push(',');
+    let file = match path {
+        Some(p) => p.to_string(),
+        None => {
+            fs::create_dir_all("export").ok();
+            format!("export/scan_{}.json", Local::now().format("%Y%m%d_%H%M%S"))
         }
-    }
-    json.push_str("]}");
+    };
+
+    let json = match pretty {
+        true => report::to_json_pretty(&app.last_results),
+        false => report::to_json(&app.last_results),
+    };
 
     match fs::write(&file, json) {
         Ok(_) => app.event(format!("Exported JSON → {}", file)),
-        Err(_) => app.event("JSON export failed"),
+        Err(e) => app.error(format!("JSON export failed: {}", e)),
+    }
+}
+
+// =======================
+// EXPORT CSV
+// =======================
+fn export_csv(app: &mut App, path: Option<&str>) {
+    if app.last_results.is_empty() {
+        app.warn("Nothing to export");
+        return;
+    }
+
+    let file = match path {
+        Some(p) => p.to_string(),
+        None => {
+            fs::create_dir_all("export").ok();
+            format!("export/scan_{}.csv", Local::now().format("%Y%m%d_%H%M%S"))
+        }
+    };
+
+    match fs::write(&file, report::to_csv(&app.last_results)) {
+        Ok(_) => app.event(format!("Exported CSV → {}", file)),
+        Err(e) => app.error(format!("CSV export failed: {}", e)),
+    }
+}
+
+// =======================
+// EXPORT NMAP XML
+// =======================
+fn export_xml(app: &mut App, path: Option<&str>) {
+    let Some(meta) = &app.last_meta else {
+        app.warn("Nothing to export");
+        return;
+    };
+    if app.last_results.is_empty() {
+        app.warn("Nothing to export");
+        return;
+    }
+
+    let file = match path {
+        Some(p) => p.to_string(),
+        None => {
+            fs::create_dir_all("export").ok();
+            format!("export/scan_{}.xml", Local::now().format("%Y%m%d_%H%M%S"))
+        }
+    };
+
+    match fs::write(&file, report::to_nmap_xml(&app.last_results, meta)) {
+        Ok(_) => app.event(format!("Exported nmap XML → {}", file)),
+        Err(e) => app.error(format!("XML export failed: {}", e)),
     }
 }
 
@@ -275,7 +1526,7 @@ fn export_json(app: &mut App) {
 // =======================
 fn export_pdf(app: &mut App) {
     if app.last_results.is_empty() {
-        app.event("Nothing to export");
+        app.warn("Nothing to export");
         return;
     }
 
@@ -303,9 +1554,10 @@ fn export_pdf(app: &mut App) {
         }
 
 let line = format!(
-            "Port {:<5} {:<8} {:<8} {} {}%",
-            r.port,
-            format!("{:?}", r.status),
+            "{} Port {:<5} {:<8} {:<8} {} {}%",
+            r.host,
+            r.port_label(),
+            r.status.to_string(),
             r.service,
             r.os_hint.unwrap_or(""),
             r.confidence
@@ -321,26 +1573,20 @@ let line = format!(
     app.event(format!("Exported PDF → {}", file_path));
 }
 
-// =======================
-// PORT PARSER
-// =======================
-fn parse_ports(raw: &str) -> Option<Ports> {
-    if raw.contains(',') {
-        Some(Ports::multiple(
-            raw.split(',').map(|p| p.parse().ok()).collect::<Option<Vec<_>>>()?,
-        ))
-    } else if raw.contains('-') {
-        let p: Vec<&str> = raw.split('-').collect();
-        Some(Ports::range(p[0].parse().ok()?, p[1].parse().ok()?))
-    } else {
-        Some(Ports::single(raw.parse().ok()?))
-    }
-}
-
 // =======================
 // UI RENDER (GRID + SCROLL)
 // =======================
 fn draw_ui(f: &mut ratatui::Frame, app: &App) {
+    // Squeezed below this, the output pane shrinks to nothing
+    if f.size().height < MIN_ROWS {
+        let msg = format!("Terminal too small (need ≥ {} rows, have {})", MIN_ROWS, f.size().height);
+        f.render_widget(
+            Paragraph::new(msg).style(app.fg(Color::Yellow)).wrap(Wrap { trim: true }),
+            f.size(),
+        );
+        return;
+    }
+
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -351,73 +1597,491 @@ fn draw_ui(f: &mut ratatui::Frame, app: &App) {
         ])
         .split(f.size());
 
+    let status = match app.state {
+        UiState::Scanning if app.pause.is_paused() => format!(
+            " WISE1738 | STATE: PAUSED | {} (Alt+P resumes) ",
+            pending_text(&app.pending)
+        ),
+        UiState::Scanning => format!(
+            " WISE1738 | STATE: SCANNING {} | {} ",
+            SPINNER[app.spinner],
+            pending_text(&app.pending)
+        ),
+        UiState::Detail => " WISE1738 | STATE: DETAIL ".to_string(),
+        UiState::Watching => match &app.watch {
+            Some(w) => format!(
+                " WISE1738 | STATE: WATCHING | run {}, next in {}s (Esc stops) ",
+                w.runs + 1,
+                w.next.saturating_duration_since(Instant::now()).as_secs()
+            ),
+            None => " WISE1738 | STATE: WATCHING ".to_string(),
+        },
+        _ => " WISE1738 | STATE: IDLE ".to_string(),
+    };
+    let status = match &app.filter {
+        Some(filter) => format!("{}| FILTER: {} ", status, filter),
+        None => status,
+    };
+
+    // While scanning, the right end of the status bar is a progress gauge
+    let (status_area, gauge_area) = match (&app.state, &app.gauge) {
+        (UiState::Scanning, Some(_)) => {
+            let split = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(1), Constraint::Length(GAUGE_WIDTH)])
+                .split(layout[0]);
+            (split[0], Some(split[1]))
+        }
+        _ => (layout[0], None),
+    };
     f.render_widget(
-        Paragraph::new(" WISE1738 | STATE: IDLE ")
-            .style(Style::default().fg(Color::Gray)),
-        layout[0],
+        Paragraph::new(status)
+            .style(app.fg(Color::Gray)),
+        status_area,
     );
+    if let (Some(area), Some(gauge)) = (gauge_area, &app.gauge) {
+        let label = format!("{}/{} ({:.0}%)", gauge.completed(), gauge.total, gauge.ratio() * 100.0);
+        f.render_widget(
+            Gauge::default()
+                .gauge_style(app.fg(Color::Green))
+                .ratio(gauge.ratio())
+                .label(label)
+                .use_unicode(true),
+            area,
+        );
+    }
+
+    let command_color = if app.last_error { Color::Red } else { Color::Cyan };
+    // Without colors, a failed command still needs to stand out
+    let command_style = match (app.color, app.last_error) {
+        (false, true) => Style::default().add_modifier(Modifier::BOLD),
+        _ => app.fg(command_color),
+    };
 
+    let inner = layout[1].width.saturating_sub(2) as usize;
+    let (shown, cursor) = command_line(&app.command, app.cursor, inner);
     f.render_widget(
-        Paragraph::new(format!("> {}", app.command))
-            .block(Block::default().title(" COMMAND ").borders(Borders::ALL))
-            .style(Style::default().fg(Color::Cyan)),
+        Paragraph::new(shown)
+            .block(Block::default().title(" COMMAND (↑ ↓ history) ").borders(Borders::ALL))
+            .style(command_style),
         layout[1],
     );
+    f.set_cursor(layout[1].x + 1 + cursor as u16, layout[1].y + 1);
 
     let area = layout[2];
-    let mut lines: Vec<Line> = Vec::new();
-
-    if !app.open.is_empty() {
-        lines.push(Line::from(Span::styled(
-            "[ OPEN PORTS ]",
-            Style::default().fg(Color::Green),
-        )));
-        for l in &app.open {
-            lines.push(Line::from(Span::styled(l, Style::default().fg(Color::Green))));
-        }
-        lines.push(Line::from(""));
-    }
-
-    if !app.closed.is_empty() {
-        lines.push(Line::from(Span::styled(
-            "[ CLOSED PORTS ]",
-            Style::default().fg(Color::DarkGray),
-        )));
-
-        let col_width = 28;
-        let cols = (area.width as usize / col_width).max(1);
-        let rows = (app.closed.len() + cols - 1) / cols;
-
-        for r in 0..rows {
-            let mut spans = Vec::new();
-            for c in 0..cols {
-                let i = r + c * rows;
-                if let Some(item) = app.closed.get(i) {
-                    spans.push(Span::styled(
-                        format!("{:<width$}", item, width = col_width),
-                        Style::default().fg(Color::DarkGray),
-                    ));
-                }
+
+    let (output_title, events_title) = match app.focus {
+        Focus::Output => (" SCAN OUTPUT (PgUp PgDn, Alt+O/Alt+C) ", " EVENTS (Tab) "),
+        Focus::Events => (" SCAN OUTPUT (Tab) ", " EVENTS (PgUp PgDn) "),
+    };
+    let output_block = Block::default().title(output_title).borders(Borders::ALL);
+
+    // Row under the cursor, for the detail popup; the summary and help have none
+    let selected = if app.help || app.view == View::Summary {
+        let lines: Vec<Line> = match app.help {
+            true => HELP.iter().map(|l| Line::from(*l)).collect(),
+            false => summary_lines(app)
+                .into_iter()
+                .map(|l| Line::from(Span::styled(l, app.fg(Color::Green))))
+                .collect(),
+        };
+
+        let visible = area.height.saturating_sub(2) as usize;
+        let max_scroll = lines.len().saturating_sub(visible);
+        app.max_scroll.set(max_scroll);
+        app.sections.set((None, None));
+        let start = app.selected.min(max_scroll);
+        let end = (start + visible).min(lines.len());
+
+        f.render_widget(Paragraph::new(lines[start..end].to_vec()).block(output_block), area);
+        None
+    } else {
+        let shown = shown_results(app);
+
+        app.sections.set((
+            shown.iter().position(|r| r.status == PortStatus::Open),
+            shown.iter().position(|r| r.status != PortStatus::Open),
+        ));
+        app.max_scroll.set(shown.len().saturating_sub(1));
+
+        let rows = shown.iter().enumerate().map(|(i, r)| {
+            let color = match r.status {
+                PortStatus::Open => Color::Green,
+                PortStatus::Unreachable => Color::Magenta,
+                _ => Color::DarkGray,
+            };
+            let mut cells = Vec::with_capacity(5);
+            // The host heads its group of rows; repeats are left blank
+            if app.multi_host {
+                let first = i == 0 || shown[i - 1].host != r.host;
+                cells.push(if first { app.host_name(&r.host).to_string() } else { String::new() });
+            }
+            let rtt = r.latency.map(latency_text).unwrap_or_default();
+            cells.extend([r.port_label(), state_label(r.status).to_string(), rtt, service_text(r)]);
+            match app.changed.iter().any(|(h, p)| *p == r.port && *h == r.host) {
+                true => Row::new(cells).style(app.fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                false => Row::new(cells).style(app.fg(color)),
             }
-            lines.push(Line::from(spans));
+        });
+
+        let mut header = vec!["PORT", "STATE", "RTT", "SERVICE"];
+        // UDP rows are wider: `53/udp`, `OPEN|FILTERED`, `UNREACHABLE`
+        let port_width = shown.iter().map(|r| r.port_label().len()).max().unwrap_or(0).max(5);
+        let state_width = shown.iter().map(|r| state_label(r.status).len()).max().unwrap_or(0).max(7);
+        let mut widths = vec![
+            Constraint::Length(port_width as u16),
+            Constraint::Length(state_width as u16),
+            Constraint::Length(7),
+            Constraint::Min(10),
+        ];
+        if app.multi_host {
+            let host_width = shown.iter().map(|r| app.host_name(&r.host).width()).max().unwrap_or(4).max(4);
+            header.insert(0, "HOST");
+            widths.insert(0, Constraint::Length(host_width as u16));
         }
+
+        let table = Table::new(rows, widths)
+            .header(Row::new(header).style(app.fg(Color::Gray).add_modifier(Modifier::BOLD)))
+            .block(output_block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        let mut state = app.table.take();
+        state.select((!shown.is_empty()).then_some(app.selected.min(shown.len().saturating_sub(1))));
+        f.render_stateful_widget(table, area, &mut state);
+        app.table.set(state);
+        shown.get(app.selected).copied()
+    };
+
+    if matches!(app.state, UiState::Detail)
+        && let Some(r) = selected
+    {
+        draw_detail(f, area, r, app.host_name(&r.host), app.fg(Color::Cyan));
     }
 
-    let visible = area.height.saturating_sub(2) as usize;
-    let max_scroll = lines.len().saturating_sub(visible);
-    let start = app.scroll.min(max_scroll);
-    let end = (start + visible).min(lines.len());
+    // Newest events at the bottom, scrolled back by events_scroll
+    let visible = layout[3].height.saturating_sub(2) as usize;
+    app.events_rows.set(visible);
+    let back = app.events_scroll.min(app.max_events_scroll());
+    let end = app.events.len() - back;
+    let start = end.saturating_sub(visible);
+    let shown: Vec<Line> = app
+        .events
+        .range(start..end)
+        .map(|e| {
+            let style = match e.level {
+                Level::Info => Style::default(),
+                Level::Warn => app.fg(Color::Yellow),
+                Level::Error => app.fg(Color::Red),
+            };
+            Line::styled(format!("[{}] {}", e.ts, e.msg), style)
+        })
+        .collect();
 
     f.render_widget(
-        Paragraph::new(lines[start..end].to_vec())
-            .block(Block::default().title(" SCAN OUTPUT (↑ ↓) ").borders(Borders::ALL)),
-        area,
+        Paragraph::new(shown)
+            .block(Block::default().title(events_title).borders(Borders::ALL)),
+        layout[3],
     );
+}
+
+/// Open ports aggregated per host, hosts in scan order
+fn summary_lines(app: &App) -> Vec<String> {
+    let mut hosts: Vec<(&str, Vec<&ScanResult>)> = Vec::new();
+
+    for r in &app.last_results {
+        let i = match hosts.iter().position(|(h, _)| *h == r.host) {
+            Some(i) => i,
+            None => {
+                hosts.push((&r.host, Vec::new()));
+                hosts.len() - 1
+            }
+        };
+        if r.status == PortStatus::Open {
+            hosts[i].1.push(r);
+        }
+    }
 
+    hosts
+        .into_iter()
+        .map(|(host, open)| {
+            let host = app.host_name(host);
+            if open.is_empty() {
+                return format!("{}: no open ports", host);
+            }
+            let mut ports: Vec<u16> = open.iter().map(|r| r.port).collect();
+            ports.sort_unstable();
+            let ports: Vec<String> = ports.iter().map(u16::to_string).collect();
+            let open: Vec<ScanResult> = open.into_iter().cloned().collect();
+            match engine::os_guess(&open) {
+                Some(os) => format!("{}: {} open  (OS guess: {}, low confidence)", host, ports.join(","), os),
+                None => format!("{}: {} open", host, ports.join(",")),
+            }
+        })
+        .collect()
+}
+
+/// Table rows: open ports first, then (View::All) everything else;
+/// only those matching the `filter`, if one is set. With several hosts
+/// each host's rows stay together (hosts in scan order), open first
+/// within each, so the blank host cells below a name are its own.
+fn shown_results(app: &App) -> Vec<&ScanResult> {
+    let mut shown: Vec<&ScanResult> = app
+        .last_results
+        .iter()
+        .filter(|r| app.view == View::All || r.status == PortStatus::Open)
+        .filter(|r| app.filter.as_deref().is_none_or(|f| matches_filter(r, f)))
+        .collect();
+
+    let mut hosts: HashMap<&str, usize> = HashMap::new();
+    if app.multi_host {
+        for r in &shown {
+            let next = hosts.len();
+            hosts.entry(&r.host).or_insert(next);
+        }
+    }
+    // Stable: ports keep their order within a host and state
+    shown.sort_by_key(|r| (hosts.get(r.host.as_str()).copied(), r.status != PortStatus::Open));
+    shown
+}
+
+/// `filter` (lower-case) in the port, state or service column, or the
+/// status name (`ProxyDenied` rows read DENIED), ignoring case
+fn matches_filter(r: &ScanResult, filter: &str) -> bool {
+    // One lower-cased line per row; tabs keep a match inside one column
+    let text = format!("{}\t{}\t{}\t{}", r.port_label(), state_label(r.status), r.status, service_text(r));
+    text.to_lowercase().contains(filter)
+}
+
+fn selected_result(app: &App) -> Option<&ScanResult> {
+    if app.help || app.view == View::Summary {
+        return None;
+    }
+    shown_results(app).get(app.selected).copied()
+}
+
+/// Popup over the output pane with everything known about one port
+fn draw_detail(f: &mut ratatui::Frame, area: Rect, r: &ScanResult, name: &str, style: Style) {
+    let or_dash = |v: Option<String>| v.unwrap_or_else(|| "-".into());
+    let host = match name == r.host {
+        true => r.host.clone(),
+        false => format!("{} ({})", name, r.host),
+    };
+    let mut text = vec![
+        format!("Host:       {}", host),
+        format!("Port:       {}", r.port_label()),
+        format!("State:      {}", r.status),
+        format!("Address:    {}", or_dash(r.remote_addr.map(|a| a.to_string()))),
+        format!("Local:      {}", or_dash(r.local_addr.map(|a| a.to_string()))),
+        format!("RTT:        {}", or_dash(r.latency.map(latency_text))),
+        format!("Service:    {}", r.service),
+        format!("OS hint:    {}", r.os_hint.unwrap_or("-")),
+        format!("Confidence: {}%", r.confidence),
+        format!("Detail:     {}", or_dash(r.detail.clone())),
+        format!("Banner:     {}", or_dash(r.banner.clone())),
+    ];
+    if let Some(tls) = &r.tls_info {
+        let alpn = tls.alpn.as_ref().map(|p| format!(" ({})", p)).unwrap_or_default();
+        text.extend([
+            format!("TLS:        {}{}", tls.protocol, alpn),
+            format!("Cert CN:    {}", or_dash(tls.common_name.clone())),
+            format!("Cert SAN:   {}", or_dash((!tls.san.is_empty()).then(|| tls.san.join(", ")))),
+            format!("Expires:    {}", or_dash(tls.not_after.clone())),
+        ]);
+    }
+
+    let width = area.width.saturating_sub(4).min(80);
+    // A long banner wraps over several rows
+    let inner = width.saturating_sub(2).max(1) as usize;
+    let rows: usize = text.iter().map(|l| l.width().div_ceil(inner).max(1)).sum();
+    let height = (rows as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    f.render_widget(Clear, popup);
     f.render_widget(
-        Paragraph::new(app.events.join("\n"))
-            .block(Block::default().title(" EVENTS ").borders(Borders::ALL)),
-        layout[3],
+        Paragraph::new(text.join("\n"))
+            .wrap(Wrap { trim: false })
+            .block(Block::default().title(" DETAILS (Esc) ").borders(Borders::ALL))
+            .style(style),
+        popup,
     );
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(port: u16, status: PortStatus, service: &str) -> ScanResult {
+        ScanResult {
+            host: "h".into(),
+            port,
+            protocol: ports::Protocol::Tcp,
+            status,
+            service: service.into(),
+            os_hint: None,
+            confidence: 0,
+            detail: None,
+            remote_addr: None,
+            local_addr: None,
+            banner: None,
+            latency: None,
+            tls_info: None,
+        }
+    }
+
+    fn shown_ports(app: &App) -> Vec<u16> {
+        shown_results(app).iter().map(|r| r.port).collect()
+    }
+
+    fn submit(app: &mut App, cmd: &str) {
+        app.command = cmd.to_string();
+        app.take_command();
+    }
+
+    #[test]
+    fn history_skips_repeats_and_stays_capped() {
+        let mut app = App::new();
+        submit(&mut app, "scan");
+        submit(&mut app, " scan ");
+        submit(&mut app, "");
+        submit(&mut app, "help");
+        submit(&mut app, "scan");
+        assert_eq!(app.history, ["scan", "help", "scan"]);
+
+        for i in 0..COMMAND_HISTORY + 5 {
+            submit(&mut app, &format!("filter {}", i));
+        }
+        assert_eq!(app.history.len(), COMMAND_HISTORY);
+        assert_eq!(app.history[0], "filter 5");
+        assert_eq!(app.history.last().unwrap(), &format!("filter {}", COMMAND_HISTORY + 4));
+    }
+
+    #[test]
+    fn loaded_report_fills_the_summary_and_metrics() {
+        let report = ScanReport {
+            target: "a,b".into(),
+            started_at: chrono::Utc::now(),
+            duration: Duration::from_millis(1500),
+            results: vec![
+                ScanResult { host: "a".into(), ..row(5000, PortStatus::Open, "unknown") },
+                ScanResult { host: "a".into(), ..row(23, PortStatus::Closed, "unknown") },
+                ScanResult { host: "b".into(), ..row(80, PortStatus::Filtered, "unknown") },
+            ],
+            errors: Vec::new(),
+        };
+        let path = std::env::temp_dir().join(format!("wise1738-load-{}.json", std::process::id()));
+        fs::write(&path, report::report_json(&report)).unwrap();
+
+        let mut app = App::new();
+        load_report(&mut app, path.to_str().unwrap());
+        fs::remove_file(&path).ok();
+
+        assert_eq!(summary_lines(&app), ["a: 5000 open", "b: no open ports"]);
+        let meta = app.last_meta.as_ref().unwrap();
+        assert_eq!((meta.metrics.ports, meta.metrics.open, meta.metrics.closed, meta.metrics.filtered), (3, 1, 1, 1));
+        assert_eq!(meta.metrics.elapsed, Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn events_scroll_stops_at_the_oldest_page() {
+        let mut app = App::new();
+        app.events_rows.set(5);
+        app.focus = Focus::Events;
+        for i in 0..20 {
+            app.event(format!("event {}", i));
+        }
+        let max = app.events.len() - 5;
+
+        app.scroll_up(PAGE_ROWS);
+        assert_eq!(app.events_scroll, PAGE_ROWS);
+        app.scroll_up(1000);
+        assert_eq!(app.events_scroll, max);
+
+        // A new event keeps the view pinned, but never past the oldest page
+        app.event("one more");
+        assert_eq!(app.events_scroll, max + 1);
+        app.event("and another");
+        assert_eq!(app.events_scroll, app.events.len() - 5);
+
+        app.scroll_down(1000);
+        assert_eq!(app.events_scroll, 0);
+    }
+
+    #[test]
+    fn command_line_counts_display_columns() {
+        assert_eq!(command_line("scan", 4, 20), ("> scan".into(), 6));
+        assert_eq!(command_line("scan", 0, 20), ("> scan".into(), 2));
+        // Wide characters take two columns, a combining accent none
+        assert_eq!(command_line("日本", 6, 20), ("> 日本".into(), 6));
+        assert_eq!(command_line("e\u{301}x", 3, 20), ("> e\u{301}x".into(), 3));
+    }
+
+    #[test]
+    fn command_line_scrolls_to_keep_the_cursor_visible() {
+        assert_eq!(command_line("abcdefgh", 8, 6), ("defgh".into(), 5));
+        assert_eq!(command_line("abcdefgh", 2, 6), ("> abcdefgh".into(), 4));
+        // Whole characters drop off the front, never half of a wide one
+        assert_eq!(command_line("日本語", 9, 5), ("本語".into(), 4));
+        assert_eq!(command_line("ab", 2, 0), ("".into(), 0));
+    }
+
+    #[test]
+    fn editing_moves_over_whole_graphemes() {
+        let mut app = App::new();
+        for c in "ae\u{301}日".chars() {
+            app.insert(c);
+        }
+        assert_eq!(app.cursor, app.command.len());
+
+        app.cursor_left();
+        assert_eq!(&app.command[app.cursor..], "日");
+        app.cursor_left();
+        assert_eq!(app.cursor, 1);
+        app.cursor_right();
+        app.backspace();
+        assert_eq!(app.command, "a日");
+        assert_eq!(app.cursor, 1);
+    }
+
+    #[test]
+    fn filter_matches_port_state_and_service() {
+        let r = row(8080, PortStatus::Filtered, "HTTP-ALT");
+        assert!(matches_filter(&r, "808"));
+        assert!(matches_filter(&r, "filtered"));
+        assert!(matches_filter(&r, "http-alt"));
+        assert!(!matches_filter(&r, "ssh"));
+
+        let denied = row(443, PortStatus::ProxyDenied, "HTTPS");
+        assert!(matches_filter(&denied, "denied"));
+        assert!(matches_filter(&denied, "proxydenied"));
+    }
+
+    #[test]
+    fn filter_narrows_the_table_until_cleared() {
+        let mut app = App::new();
+        app.last_results = vec![
+            row(22, PortStatus::Open, "SSH-2.0-OpenSSH_9.6"),
+            row(80, PortStatus::Closed, "HTTP"),
+            row(8080, PortStatus::Filtered, "unknown"),
+        ];
+
+        handle_command("filter OpenSSH", &mut app);
+        assert_eq!(app.filter.as_deref(), Some("openssh"));
+        assert_eq!(shown_ports(&app), vec![22]);
+
+        handle_command("filter closed", &mut app);
+        assert_eq!(shown_ports(&app), vec![80]);
+
+        handle_command("filter 80", &mut app);
+        assert_eq!(shown_ports(&app), vec![80, 8080]);
+
+        handle_command("filter clear", &mut app);
+        assert_eq!(app.filter, None);
+        assert_eq!(shown_ports(&app), vec![22, 80, 8080]);
+        assert_eq!(app.last_results.len(), 3);
+    }
+}
@@ -0,0 +1,638 @@
+use std::{collections::BTreeSet, net::TcpListener, time::Instant};
+
+use wise1738::core::{
+    engine,
+    ports::{Ports, Protocol},
+    report,
+    scanner::{self, PortStatus},
+    target::Target,
+};
+
+/// TCP result on host `h` with every optional field empty; tests fill
+/// in what they need with `..result(..)`
+fn result(port: u16, status: PortStatus, service: &str) -> scanner::ScanResult {
+    scanner::ScanResult {
+        host: "h".into(),
+        port,
+        protocol: Protocol::Tcp,
+        status,
+        service: service.into(),
+        os_hint: None,
+        confidence: 0,
+        detail: None,
+        remote_addr: None,
+        local_addr: None,
+        banner: None,
+        latency: None,
+        tls_info: None,
+    }
+}
+
+#[test]
+fn listening_port_is_open() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let results = scanner::scan(&Target::new("127.0.0.1"), &Ports::single(port));
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].port, port);
+    assert_eq!(results[0].status, PortStatus::Open);
+}
+
+#[test]
+fn open_ports_carry_their_connect_latency() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap().port();
+
+    let results = scanner::scan(&Target::new("127.0.0.1"), &Ports::multiple(vec![1, open]));
+
+    assert_eq!(results[0].latency, None);
+    let latency = results[1].latency.expect("open port without latency");
+    assert!(latency < std::time::Duration::from_secs(1), "{:?}", latency);
+}
+
+#[test]
+fn unbound_port_is_closed() {
+    // Grab a free ephemeral port, then release it
+    let port = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    };
+
+    let results = scanner::scan(&Target::new("127.0.0.1"), &Ports::single(port));
+
+    assert_eq!(results[0].status, PortStatus::Closed);
+}
+
+#[test]
+fn ipv6_loopback_scans_with_or_without_brackets() {
+    use wise1738::core::scanner::{AddressFamily, ScanConfig};
+
+    // Hosts without IPv6 have nothing to test
+    let Ok(listener) = TcpListener::bind("[::1]:0") else {
+        return;
+    };
+    let port = listener.local_addr().unwrap().port();
+    let config = ScanConfig {
+        family: AddressFamily::V6,
+        ..Default::default()
+    };
+
+    for input in ["::1", "[::1]"] {
+        let target = Target::new(input);
+        assert_eq!(target.host, "::1");
+        let (results, meta) = engine::run_with_config(&[target], Ports::single(port), &config);
+        assert!(meta.errors.is_empty(), "{:?}", meta.errors);
+        assert_eq!(results[0].status, PortStatus::Open);
+        assert_eq!(results[0].remote_addr.map(|a| a.is_ipv6()), Some(true));
+    }
+}
+
+#[test]
+fn forcing_v4_on_a_v6_only_host_is_an_error() {
+    use wise1738::core::scanner::{AddressFamily, ScanConfig, ScanError};
+
+    let config = ScanConfig {
+        family: AddressFamily::V4,
+        ..Default::default()
+    };
+    let (results, meta) = engine::run_with_config(&[Target::new("[::1]")], Ports::single(1), &config);
+
+    assert!(results.is_empty());
+    assert_eq!(
+        meta.errors,
+        vec![ScanError::NoAddressOfFamily {
+            host: "::1".into(),
+            family: AddressFamily::V4,
+        }]
+    );
+}
+
+#[test]
+fn udp_reply_unreachable_and_silence() {
+    use std::{net::UdpSocket, thread, time::Duration};
+    use wise1738::core::scanner::ScanConfig;
+
+    let echo = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let closed = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let (echo_port, silent_port) = (echo.local_addr().unwrap().port(), silent.local_addr().unwrap().port());
+    thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        while let Ok((n, from)) = echo.recv_from(&mut buf) {
+            let _ = echo.send_to(&buf[..n], from);
+        }
+    });
+
+    let config = ScanConfig {
+        timeout: Some(Duration::from_millis(300)),
+        ..Default::default()
+    };
+    let ports = Ports::multiple(vec![echo_port, silent_port, closed]).with_protocol(Protocol::Udp);
+    let results = scanner::scan_with_config(&Target::new("127.0.0.1"), &ports, &config);
+    let status = |port| results.iter().find(|r| r.port == port).unwrap().status;
+
+    assert!(results.iter().all(|r| r.protocol == Protocol::Udp));
+    assert_eq!(status(echo_port), PortStatus::Open);
+    assert_eq!(status(silent_port), PortStatus::OpenFiltered);
+    assert_eq!(status(closed), PortStatus::Closed);
+    assert_eq!(PortStatus::OpenFiltered.to_string(), "Open|Filtered");
+}
+
+#[test]
+fn blackholed_address_is_filtered() {
+    // TEST-NET-1 (RFC 5737) is never routed; the connect should time out.
+    // Sandboxes without a default route fail fast with "unreachable"
+    // instead, so only insist on Filtered when we actually waited.
+    let started = Instant::now();
+    let results = scanner::scan(&Target::new("192.0.2.1"), &Ports::single(80));
+    let waited = started.elapsed();
+
+    assert_ne!(results[0].status, PortStatus::Open);
+    if waited.as_millis() >= 500 {
+        assert_eq!(results[0].status, PortStatus::Filtered);
+    }
+}
+
+#[test]
+fn low_timeout_against_blackhole_is_filtered() {
+    use std::time::Duration;
+    use wise1738::core::scanner::ScanConfig;
+
+    let config = ScanConfig {
+        timeout: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let results = scanner::scan_with_config(&Target::new("192.0.2.1"), &Ports::multiple(vec![80, 443]), &config);
+    let waited = started.elapsed();
+
+    // Well under the 700ms default, and the same sandbox caveat as above
+    assert!(waited < Duration::from_millis(600), "took {:?}", waited);
+    assert!(results.iter().all(|r| r.status != PortStatus::Open));
+    if waited >= Duration::from_millis(100) {
+        assert!(results.iter().all(|r| r.status == PortStatus::Filtered));
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_backend_matches_threads() {
+    use wise1738::core::scanner::{Backend, ScanConfig};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap().port();
+    let target = Target::new("127.0.0.1");
+    let ports = Ports::multiple(vec![open, 1]);
+
+    let config = ScanConfig {
+        backend: Backend::Async,
+        ..Default::default()
+    };
+    let results = scanner::scan_with_config(&target, &ports, &config);
+    let threaded = scanner::scan(&target, &ports);
+
+    let got: Vec<_> = results.iter().map(|r| (r.port, r.status)).collect();
+    let want: Vec<_> = threaded.iter().map(|r| (r.port, r.status)).collect();
+    assert_eq!(got, want);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_backend_honours_the_rate_cap() {
+    use std::time::{Duration, Instant};
+    use wise1738::core::scanner::{Backend, ScanConfig};
+
+    let config = ScanConfig {
+        backend: Backend::Async,
+        max_rate: Some(50),
+        connect_only: true,
+        ..Default::default()
+    };
+    let ports = Ports::range(1, 100);
+
+    let started = Instant::now();
+    let results = scanner::scan_with_config(&Target::new("127.0.0.1"), &ports, &config);
+
+    assert_eq!(results.len(), 100);
+    assert!(started.elapsed() >= Duration::from_millis(1950), "{:?}", started.elapsed());
+}
+
+#[test]
+fn port_that_drops_every_connection_has_no_service() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            drop(stream);
+        }
+    });
+
+    let results = scanner::scan(&Target::new("127.0.0.1"), &Ports::single(port));
+
+    assert_eq!(results[0].status, PortStatus::Open);
+    assert_eq!(results[0].service, scanner::NO_SERVICE);
+}
+
+#[test]
+fn open_only_drops_closed_results() {
+    use wise1738::core::scanner::ScanConfig;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap().port();
+    let config = ScanConfig {
+        open_only: true,
+        ..Default::default()
+    };
+
+    let results = scanner::scan_with_config(
+        &Target::new("127.0.0.1"),
+        &Ports::multiple(vec![open, 1]),
+        &config,
+    );
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].port, open);
+}
+
+#[test]
+fn http_proxy_denial_is_distinct_from_closed() {
+    use std::io::{BufRead, BufReader, Write};
+    use wise1738::core::{proxy::Proxy, scanner::ScanConfig};
+
+    // Fake proxy: tunnels to port 80, refuses (403) everything else
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let reply = if line.contains(":80 ") {
+                "HTTP/1.1 200 Connection established\r\n\r\n"
+            } else {
+                "HTTP/1.1 403 Forbidden\r\n\r\n"
+            };
+            stream.write_all(reply.as_bytes()).unwrap();
+        }
+    });
+
+    let config = ScanConfig {
+        proxy: Some(Proxy::Http(proxy)),
+        ..Default::default()
+    };
+    let results = scanner::scan_with_config(
+        &Target::new("127.0.0.1"),
+        &Ports::multiple(vec![80, 443]),
+        &config,
+    );
+
+    assert_eq!(results[0].status, PortStatus::Open);
+    assert_eq!(results[1].status, PortStatus::ProxyDenied);
+}
+
+#[test]
+fn scan_args_reject_unknown_flags_and_missing_values() {
+    use wise1738::cli::ScanArgs;
+
+    assert!(ScanArgs::parse(&["127.0.0.1", "80", "--connect-only"]).is_ok());
+    assert!(ScanArgs::parse(&["-a", "127.0.0.1", "-p", "-"]).is_ok());
+    for (args, err) in [
+        (&["127.0.0.1", "80", "--bogus-flag"][..], "Unknown flag '--bogus-flag'"),
+        (&["127.0.0.1", "80", "443"][..], "Unexpected argument '443'"),
+        (&["-a", "127.0.0.1", "-p"][..], "-p needs a value"),
+        (&["-a", "-p", "80"][..], "-a needs a value"),
+        (&["127.0.0.1", "--timeout", "--fast"][..], "--timeout needs a value"),
+    ] {
+        assert_eq!(ScanArgs::parse(args).err().as_deref(), Some(err), "{:?}", args);
+    }
+}
+
+#[test]
+fn cli_exits_2_on_a_bad_flag() {
+    let bin = env!("CARGO_BIN_EXE_wise1738");
+    for args in [&["--cli", "127.0.0.1", "80", "--bogus-flag"][..], &["--bench", "--rounds"][..]] {
+        let out = std::process::Command::new(bin).args(args).output().unwrap();
+        assert_eq!(out.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Usage: wise1738"), "{:?}", args);
+    }
+}
+
+#[test]
+fn help_prints_usage_without_starting_the_tui() {
+    for flag in ["--help", "-h"] {
+        let out = std::process::Command::new(env!("CARGO_BIN_EXE_wise1738")).arg(flag).output().unwrap();
+        assert_eq!(out.status.code(), Some(0), "{}", flag);
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.starts_with("Usage: wise1738 [--inline]"), "{}", stdout);
+        assert!(stdout.contains("wise1738 --bench"), "{}", stdout);
+    }
+}
+
+/// `wise1738 --stdin <args>` with `input` piped in
+fn stdin_scan(input: &[u8], args: &[&str]) -> std::process::Output {
+    use std::{io::Write, process::Stdio};
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_wise1738"))
+        .arg("--stdin")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn stdin_targets_skip_blanks_and_comments() {
+    let out = stdin_scan(b"# lab hosts\n\n  127.0.0.1  \n# 10.0.0.1\n127.0.0.2\n", &["1", "--connect-only"]);
+    assert_eq!(out.status.code(), Some(0));
+
+    let hosts: BTreeSet<String> = String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .map(|l| l.split("\"host\":\"").nth(1).unwrap().split('"').next().unwrap().to_string())
+        .collect();
+    assert_eq!(hosts, BTreeSet::from(["127.0.0.1".to_string(), "127.0.0.2".to_string()]));
+}
+
+#[test]
+fn stdin_rejects_missing_or_bad_targets() {
+    let cases: [(&[u8], &[&str], &str); 6] = [
+        (b"\n# nothing here\n   \n", &["1"], "No targets on stdin"),
+        (b"\xff\xfe\n", &["1"], "Cannot read stdin"),
+        (b"10.0.0.0/99\n", &["1"], "Invalid target"),
+        (b"127.0.0.1\n", &["1", "--bogus-flag"], "wise1738 --stdin"),
+        (b"127.0.0.1\n", &["1", "--json"], "--stdin always writes NDJSON"),
+        (b"127.0.0.1\n", &["1", "--json-pretty"], "--stdin always writes NDJSON"),
+    ];
+    for (input, args, err) in cases {
+        let out = stdin_scan(input, args);
+        assert_eq!(out.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&out.stderr).contains(err), "{}", err);
+        assert!(out.stdout.is_empty(), "{}", err);
+    }
+}
+
+#[test]
+fn cli_hosts_share_their_embedded_port() {
+    let cli = |hosts: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_wise1738"))
+            .args(["--cli", hosts, "--connect-only"])
+            .output()
+            .unwrap()
+    };
+
+    let mixed = cli("127.0.0.1:1,localhost:2");
+    assert_eq!(mixed.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&mixed.stderr).contains("name different ports"));
+
+    let shared = cli("127.0.0.1:1,localhost:1");
+    assert_eq!(shared.status.code(), Some(0));
+    let rows: Vec<String> = String::from_utf8_lossy(&shared.stdout).lines().map(String::from).collect();
+    assert_eq!(rows.len(), 2, "{:?}", rows);
+    assert!(rows.iter().all(|r| r.split_whitespace().nth(1) == Some("1")), "{:?}", rows);
+}
+
+#[test]
+fn pretty_json_matches_compact() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let results = scanner::scan(&Target::new("127.0.0.1"), &Ports::multiple(vec![port, 1]));
+
+    let pretty = report::to_json_pretty(&results);
+    let squeezed: String = pretty.lines().map(str::trim).collect::<Vec<_>>().join("").replace("\": ", "\":");
+
+    assert!(pretty.contains("\n    {\n      \"host\": \"127.0.0.1\",\n"));
+    assert_eq!(squeezed, report::to_json(&results));
+}
+
+#[test]
+fn cli_json_pretty_prints_one_array_for_every_host() {
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_wise1738"))
+        .args(["--cli", "127.0.0.1,localhost", "1", "--connect-only", "--json-pretty"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(0));
+
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let text = stdout.trim_end();
+    assert!(text.starts_with("[\n  {\n    \"host\": "), "{}", text);
+    assert!(text.ends_with("\n  }\n]"), "{}", text);
+    assert_eq!(text.matches("\n  },\n  {\n").count(), 1, "{}", text);
+    assert_eq!(report::results_json_pretty(&[]), "[]");
+}
+
+#[test]
+fn run_to_json_lists_every_scanned_port() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let json = engine::run_to_json("127.0.0.1", Ports::multiple(vec![port, 1]));
+
+    assert!(json.starts_with("{\"results\":["));
+    assert!(json.contains(&format!("\"port\":{},\"protocol\":\"tcp\",\"status\":\"Open\"", port)));
+    assert!(json.contains("\"port\":1,\"protocol\":\"tcp\",\"status\":\"Closed\",\"service\":\"system\""));
+}
+
+#[test]
+fn scan_report_groups_results_by_status() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let report = engine::run("127.0.0.1", Ports::multiple(vec![port, 1, 2]));
+
+    assert_eq!(report.target, "127.0.0.1");
+    assert_eq!(report.results().len(), 3);
+    assert_eq!(report.open().iter().map(|r| r.port).collect::<Vec<_>>(), vec![port]);
+    assert_eq!(report.closed().len(), 2);
+    assert!(report.filtered().is_empty());
+    assert!(report.started_at <= chrono::Utc::now());
+}
+
+#[test]
+fn builder_scans_the_port_in_the_host() {
+    use wise1738::core::engine::ScanBuilder;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let host = format!("127.0.0.1:{}", port);
+    let ports = |report: &engine::ScanReport| report.results().iter().map(|r| r.port).collect::<Vec<_>>();
+
+    // No ports given: only the embedded one, not top_basic
+    let alone = ScanBuilder::new(&host).connect_only(true).run();
+    assert_eq!(ports(&alone), vec![port]);
+    assert_eq!(alone.open().len(), 1);
+
+    // Ports given: the embedded one joins them
+    let joined = engine::run(&host, Ports::single(1));
+    let mut expected = vec![1, port];
+    expected.sort_unstable();
+    assert_eq!(ports(&joined), expected);
+}
+
+#[test]
+fn builder_reports_why_a_host_was_not_scanned() {
+    use std::net::UdpSocket;
+    use wise1738::core::{
+        engine::ScanBuilder,
+        scanner::{AddressFamily, ScanConfig, ScanError},
+    };
+
+    // Nameserver that answers every query with NXDOMAIN
+    let dns = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let resolver = dns.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        while let Ok((n, peer)) = dns.recv_from(&mut buf) {
+            buf[2] |= 0x80;
+            buf[3] = (buf[3] & 0xf0) | 3;
+            let _ = dns.send_to(&buf[..n], peer);
+        }
+    });
+
+    let report = ScanBuilder::new("nowhere.test")
+        .ports(Ports::single(80))
+        .config(ScanConfig {
+            resolver: Some(resolver),
+            ..Default::default()
+        })
+        .run();
+    assert!(report.results.is_empty());
+    assert!(
+        matches!(&report.errors[..], [ScanError::Unresolved { host, .. }] if host == "nowhere.test"),
+        "{:?}",
+        report.errors
+    );
+
+    let report = ScanBuilder::new("127.0.0.1").ports(Ports::single(1)).family(AddressFamily::V6).run();
+    assert!(report.results.is_empty());
+    assert!(matches!(report.errors[..], [ScanError::NoAddressOfFamily { .. }]), "{:?}", report.errors);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn scan_result_is_serializable() {
+    fn assert_serialize<T: serde::Serialize>(_: &T) {}
+
+    let results = scanner::scan(&Target::new("127.0.0.1"), &Ports::single(1));
+    assert_serialize(&results);
+}
+
+#[test]
+fn csv_has_a_row_per_port_and_quotes_when_needed() {
+    let results = [
+        result(22, PortStatus::Open, "SSH-2.0-Acme, \"build 7\""),
+        result(23, PortStatus::Closed, "TELNET"),
+        result(81, PortStatus::Filtered, "unknown"),
+        scanner::ScanResult {
+            protocol: Protocol::Udp,
+            ..result(53, PortStatus::OpenFiltered, "DNS")
+        },
+    ];
+
+    assert_eq!(
+        report::to_csv(&results),
+        "port,status,service\n\
+         22,Open,\"SSH-2.0-Acme, \"\"build 7\"\"\"\n\
+         23,Closed,TELNET\n\
+         81,Filtered,unknown\n\
+         53/udp,Open|Filtered,DNS\n"
+    );
+    assert_eq!(report::to_csv(&[]), "port,status,service\n");
+}
+
+#[test]
+fn saved_report_loads_back() {
+    use wise1738::core::tls::TlsInfo;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let mut report = engine::run("127.0.0.1", Ports::multiple(vec![port, 1]));
+    let mut https = report.results[0].clone();
+    https.port = 443;
+    https.service = "tab\t \"quoted\" \\ 🦀".into();
+    https.os_hint = Some("Unix-like");
+    https.banner = Some("\u{1}raw".into());
+    https.tls_info = Some(TlsInfo {
+        protocol: "TLSv1.3".into(),
+        alpn: None,
+        common_name: Some("example.com".into()),
+        san: vec!["example.com".into(), "10.0.0.1".into()],
+        not_after: Some("2027-01-15 08:00:00 UTC".into()),
+    });
+    report.results.push(https);
+
+    let saved = report::report_json(&report);
+    let loaded = report::parse_report(&saved).unwrap();
+
+    assert_eq!(loaded.target, "127.0.0.1");
+    assert_eq!(loaded.results.len(), 3);
+    assert_eq!(loaded.results[2].os_hint, Some("Unix-like"));
+    assert_eq!(loaded.results[2].tls_info, report.results[2].tls_info);
+    assert_eq!(report::report_json(&loaded), saved);
+}
+
+#[test]
+fn malformed_report_is_an_error() {
+    use report::LoadError;
+
+    let report = engine::run("127.0.0.1", Ports::single(1));
+    let saved = report::report_json(&report);
+
+    let truncated = report::parse_report(&saved[..saved.len() / 2]).unwrap_err();
+    assert!(matches!(truncated, LoadError::Syntax { .. }), "{}", truncated);
+    assert!(matches!(report::parse_report(""), Err(LoadError::Syntax { at: 0, .. })));
+    assert!(matches!(report::parse_report("{} x"), Err(LoadError::Syntax { at: 3, .. })));
+    assert_eq!(
+        report::parse_report("{\"results\":[]}").unwrap_err(),
+        LoadError::Field { result: None, field: "target" }
+    );
+
+    let bad_status = saved.replace("\"Closed\"", "\"Ajar\"");
+    let err = report::parse_report(&bad_status).unwrap_err();
+    assert_eq!(err, LoadError::Field { result: Some(0), field: "status" });
+    assert_eq!(err.to_string(), "result 1: missing or invalid \"status\"");
+    assert!(report::parse_report(&saved.replace("\"port\":1,", "\"port\":70000,")).is_err());
+    assert!(report::parse_report(&"[".repeat(100_000)).is_err());
+}
+
+#[test]
+fn nmap_xml_carries_port_state_and_service() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let targets = [Target::new("127.0.0.1")];
+    let (results, meta) = engine::run_with_config(&targets, Ports::multiple(vec![port, 1]), &Default::default());
+
+    let xml = report::to_nmap_xml(&results, &meta);
+
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE nmaprun>\n<nmaprun scanner=\"wise1738\""));
+    assert!(xml.contains("<address addr=\"127.0.0.1\" addrtype=\"ipv4\"/>"));
+    assert!(xml.contains(&format!("<port protocol=\"tcp\" portid=\"{}\"><state state=\"open\" reason=\"syn-ack\"", port)));
+    assert!(xml.contains("<port protocol=\"tcp\" portid=\"1\"><state state=\"closed\" reason=\"conn-refused\" reason_ttl=\"0\"/><service name=\"system\" method=\"table\" conf=\"3\"/></port>"));
+    assert!(xml.contains("<hosts up=\"1\" down=\"0\" total=\"1\"/>"));
+    assert!(xml.ends_with("</nmaprun>\n"));
+}
+
+#[test]
+fn os_guess_reads_ports_and_banners() {
+    let open = |port: u16, detail: Option<&str>| scanner::ScanResult {
+        detail: detail.map(String::from),
+        ..result(port, PortStatus::Open, "unknown")
+    };
+
+    assert_eq!(engine::os_guess(&[open(135, None), open(445, None), open(3389, None)]).as_deref(), Some("Windows"));
+    assert_eq!(engine::os_guess(&[open(22, None), open(80, None)]).as_deref(), Some("Unix-like"));
+    assert_eq!(
+        engine::os_guess(&[open(22, None), open(25, Some("220 mail ESMTP Postfix (Ubuntu)"))]).as_deref(),
+        Some("Linux (Ubuntu)")
+    );
+    let ssh = scanner::ScanResult {
+        service: "SSH-2.0-OpenSSH_9.2p1 Debian-2".into(),
+        ..open(22, None)
+    };
+    assert_eq!(engine::os_guess(&[ssh]).as_deref(), Some("Linux (Debian)"));
+    assert_eq!(engine::os_guess(&[open(22, None), open(445, None)]), None);
+    assert_eq!(engine::os_guess(&[open(8080, None)]), None);
+}